@@ -1,30 +1,50 @@
-use std::collections::VecDeque;
-use std::io::{self, Write};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io;
+use std::time::{Duration, Instant};
 use colored::*;
-use crate::utilities::get_current_date;
-use crate::spaced_repetition::{Evaluation, compute_sr_data};
+use rand::seq::SliceRandom;
+use crate::utilities::{get_current_date, get_commands_from_file, find_deck_files, Deck, ImportFormat};
+use crate::cards;
+use crate::challenge::{self, ChallengeRun};
+use crate::grading::{diff, grade, DiffPart, Grade};
+use crate::hints::hint_ladder;
+use crate::prompt::{Answer, AnswerPrompt, PromptOptions};
+use crate::session::{handle_interrupts, is_interrupted, save_review, ReviewResult, ReviewSession};
+use crate::spaced_repetition::{Evaluation, compute_sr_data, response_baseline, suggest_evaluation};
 use comfy_table::presets::UTF8_FULL;
 use comfy_table::{Color, *};
 use super::database;
-use super::Command;
+use super::{CardKind, Command};
 use super::Mode;
 
 fn clear() {
     print!("{esc}c", esc = 27 as char);
 }
 
-fn get_eval_from_user(is_correct: bool) -> Evaluation {
-    let mut user_input = String::new();
+enum Rating {
+    Rated(Evaluation),
+    /// The user asked to undo the previous rating instead
+    Undo,
+    Quit,
+}
 
+fn get_eval_from_user(answer_prompt: &mut AnswerPrompt, is_correct: bool, suggestion: Option<&Evaluation>) -> anyhow::Result<Rating> {
     loop {
-        user_input.clear();
-        io::stdin().read_line(&mut user_input).unwrap();
+        let Some(user_input) = answer_prompt.read_line("")? else { return Ok(Rating::Quit) };
+
+        if user_input.trim() == "u" {
+            return Ok(Rating::Undo);
+        }
+
+        if let (Some(suggestion), "") = (suggestion, user_input.trim()) {
+            return Ok(Rating::Rated(suggestion.get_num().into()));
+        }
 
         match user_input.trim().parse::<i64>() {
-            Ok(num) if num >= 1 && num <= 3 => {
+            Ok(num) if (1..=3).contains(&num) => {
                 let num = num - 1 + if is_correct { 3 } else { 0 }; // offset
                 let evaluation: Evaluation = num.into();
-                return evaluation;
+                return Ok(Rating::Rated(evaluation));
             },
             Ok(_) => println!("Number must be 1, 2 or 3. Try again."),
             Err(_) => println!("Invalid input. Please enter a valid number."),
@@ -32,97 +52,564 @@ fn get_eval_from_user(is_correct: bool) -> Evaluation {
     }
 }
 
-pub async fn practice(commands: &mut VecDeque<Command>) -> anyhow::Result<()> {
-    let mut user_input:String = String::new();
+/// Asks `commands` until all of them are answered correctly, or until
+/// `time_limit` is over, without changing their schedule.
+pub async fn practice(commands: &mut VecDeque<Command>, prompt_options: &PromptOptions, time_limit: Option<Duration>) -> anyhow::Result<()> {
+    drill(commands, prompt_options, time_limit, None).await
+}
+
+/// Asks `commands` until each of them is answered correctly `streak` times
+/// in a row, or until `time_limit` is over. The answers are saved in the
+/// cram history, apart from the reviews, and the schedule doesn't change.
+pub async fn cram(commands: &mut VecDeque<Command>, prompt_options: &PromptOptions, time_limit: Option<Duration>, streak: usize) -> anyhow::Result<()> {
+    drill(commands, prompt_options, time_limit, Some(streak.max(1))).await
+}
+
+/// The loop of `practice` and, when `streak` is given, of `cram`.
+async fn drill(commands: &mut VecDeque<Command>, prompt_options: &PromptOptions, time_limit: Option<Duration>, streak: Option<usize>) -> anyhow::Result<()> {
+    let mut answer_prompt = AnswerPrompt::new(prompt_options)?;
+    let mut user_input = String::new();
     let original_size = commands.len();
+    let mut results: Vec<ReviewResult> = vec![];
+    let practice_started = Instant::now();
+    let mut streaks: HashMap<i64, usize> = HashMap::new();
+
+    if commands.is_empty() {
+        println!("RECLI: There are no commands to practice.");
+        return Ok(());
+    }
+
+    while !commands.is_empty() {
+        if time_limit.is_some_and(|limit| practice_started.elapsed() >= limit) {
+            clear();
+            println!("RECLI: Time is up, {} commands were not practiced.", commands.len());
+            show_report(&results, None);
+            return Ok(());
+        }
 
-    while commands.len() > 0 {
         clear();
         let progress = format!("{}/{}", original_size - commands.len(), original_size);
 
         let command: Command = commands.pop_front().unwrap();
+        let options = cards::find_options(&command).await?;
+        print_question(&command, &progress);
 
-        println!("{} {} \nTask: {}\n", 
-            "RECLI".magenta().bold(),
-            progress,
-            command.task.cyan()
-        );
+        let started = Instant::now();
+        let mut hints = 0;
+        let (answer, grade, failed_steps) = match ask(&mut answer_prompt, &command, &options, &mut hints)? {
+            Asked::Answered { answer, grade, failed_steps, .. } => (answer, grade, failed_steps),
+            Asked::Undo => {
+                // Practice doesn't rate commands, so there is nothing to undo
+                commands.push_front(command);
+                continue;
+            },
+            Asked::Quit => {
+                show_report(&results, None);
+                return Ok(());
+            },
+        };
 
-        if let Some(context) = &command.context {
-            print!("{}\n\n", context.trim());
-        }
+        let is_correct = grade.is_correct();
+        let response_time = started.elapsed();
+        results.push(ReviewResult {
+            command_id: command.id.unwrap_or(0),
+            reviewed: chrono::Utc::now(),
+            mode_before: command.sr_data.mode.clone(),
+            task: command.task.clone(),
+            tags: command.tags.clone().unwrap_or_default(),
+            is_correct,
+            response_time: Some(response_time),
+            hints,
+            e_factor_before: None,
+            e_factor_after: None,
+            lapsed: false,
+            failed_steps: failed_steps.clone(),
+        });
 
-        match &command.prompt {
-            Some(prompt) => print!("{} ", prompt.trim()),
-            None => print!("> ")
+        if command.kind == CardKind::Scenario {
+            print_steps_summary(&command, &failed_steps);
         }
-
-        user_input = String::from("");
-        io::stdout().flush().unwrap();
-        io::stdin().read_line(&mut user_input).unwrap();
-
-        let is_correct = command.commands.contains(&user_input.trim().to_string());
-
-        if is_correct {
-            if let Some(response) = &command.response {
+        else if is_correct {
+            if let Some(response) = command.response.as_ref().filter(|_| command.kind != CardKind::Explain) {
                 println!("{}", response.trim());
             }
             println!("\n{}", "CORRECT!".green());
         }
         else {
-            println!("\n{}", "INCORRECT".red());
-            println!("Expected:");
-            command.commands.iter().for_each(|c| println!("{}", c.green()));
-            commands.push_back(command);
+            print_incorrect(&command, &answer, &grade);
+        }
+
+        let current = count_drill_answer(commands, &mut streaks, command, is_correct, response_time, streak).await?;
+        if let Some(streak) = streak.filter(|streak| current < *streak) {
+            println!("{}", format!("Streak: {}/{}", current, streak).cyan());
         }
         println!("{}", "Press 'Enter' to continue.".cyan());
         io::stdin().read_line(&mut user_input).unwrap();
     }
     clear();
-    println!("RECLI: Your have finished your practice.");
+    match streak {
+        Some(_) => println!("RECLI: You have finished cramming."),
+        None => println!("RECLI: Your have finished your practice."),
+    }
+    show_report(&results, None);
 
     Ok(())
 }
 
-pub async fn review(commands: &mut VecDeque<Command>) -> anyhow::Result<()> {
-    let mut user_input:String = String::new();
-    let original_size = commands.len();
+/// Counts an answer to `command` in its streak and, when cramming for a
+/// `streak`, saves it in the cram history. The command goes back to the
+/// queue until its streak is reached. Returns the current streak.
+async fn count_drill_answer(commands: &mut VecDeque<Command>, streaks: &mut HashMap<i64, usize>, command: Command, is_correct: bool, response_time: Duration, streak: Option<usize>) -> anyhow::Result<usize> {
+    // A wrong answer starts the streak of the command again
+    let current = streaks.entry(command.id.unwrap_or(0)).or_default();
+    *current = if is_correct { *current + 1 } else { 0 };
+    let current = *current;
+
+    if streak.is_some() {
+        database::save_cram_answer(&command, is_correct, response_time).await?;
+    }
+    if current < streak.unwrap_or(1) {
+        commands.push_back(command);
+    }
+
+    Ok(current)
+}
+
+/// Asks random commands until `duration` is over, scoring the correct
+/// answers by the difficulty of the commands. The run is saved in the
+/// leaderboard of `tag` and the schedule of the commands doesn't change.
+pub async fn challenge(mut commands: Vec<Command>, tag: Option<&str>, duration: Duration, prompt_options: &PromptOptions) -> anyhow::Result<()> {
+    // Explain cards are graded by the user and scenarios are too long for a speed run
+    commands.retain(|command| !matches!(command.kind, CardKind::Explain | CardKind::Scenario));
+    if commands.is_empty() {
+        println!("RECLI: There are no commands for a challenge.");
+        return Ok(());
+    }
+
+    let mut answer_prompt = AnswerPrompt::new(prompt_options)?;
+    let mut rng = rand::thread_rng();
+    let mut queue: VecDeque<Command> = VecDeque::new();
+    let mut feedback: Option<String> = None;
+    let mut run = ChallengeRun {
+        id: None,
+        tag: tag.unwrap_or_default().to_string(),
+        started: chrono::Utc::now(),
+        duration: duration.as_secs() as i64,
+        answers: 0,
+        correct: 0,
+        score: 0,
+    };
+    let started = Instant::now();
+
+    while started.elapsed() < duration {
+        if queue.is_empty() {
+            commands.shuffle(&mut rng);
+            queue.extend(commands.iter().cloned());
+        }
+        let command = queue.pop_front().unwrap();
 
-    while commands.len() > 0 {
         clear();
-        let progress = format!("{}/{}", original_size - commands.len(), original_size);
+        if let Some(feedback) = feedback.take() {
+            println!("{}\n", feedback);
+        }
+        let progress = format!("{}s left  Score: {}", duration.saturating_sub(started.elapsed()).as_secs(), run.score);
+        let options = cards::find_options(&command).await?;
+        print_question(&command, &progress);
 
-        let mut command: Command = commands.pop_front().unwrap();
+        let mut hints = 0;
+        let grade = match ask(&mut answer_prompt, &command, &options, &mut hints)? {
+            Asked::Answered { grade, .. } => grade,
+            Asked::Undo => continue,
+            Asked::Quit => break,
+        };
 
-        println!("{} {} \nTask: {}\n", 
-            "RECLI".magenta().bold(),
-            progress,
-            command.task.cyan()
-        );
+        // Answers given after the time is up don't count
+        if started.elapsed() >= duration {
+            break;
+        }
+
+        run.answers += 1;
+        feedback = Some(match grade {
+            Grade::Exact => {
+                // Every hint halves the points
+                let points = challenge::points(command.sr_data.e_factor) >> hints.min(8);
+                run.correct += 1;
+                run.score += points;
+                format!("{} +{}", "CORRECT!".green(), points)
+            },
+            Grade::NearMiss { expected } | Grade::Wrong { expected } => {
+                format!("{} Expected: {}", "INCORRECT".red(), expected.green())
+            },
+        });
+    }
+
+    let best = database::find_challenge_runs(tag).await?.first().map(|best| best.score);
+    run.id = Some(database::save_challenge_run(&run).await?);
+
+    clear();
+    println!("RECLI: Time is up! You scored {} points, {} of {} answers were correct.",
+        run.score.to_string().cyan(),
+        run.correct,
+        run.answers
+    );
+    if run.score > 0 && best.is_none_or(|best| run.score > best) {
+        println!("{}", "New personal best!".green().bold());
+    }
+    challenge::show_leaderboard(tag, &database::find_challenge_runs(tag).await?, Some(&run));
+
+    Ok(())
+}
+
+/// Shows the expected commands and how the answer differs from the closest one.
+fn print_incorrect(command: &Command, answer: &str, grade: &Grade) {
+    let expected = match grade {
+        Grade::NearMiss { expected } => {
+            println!("\n{}", "ALMOST".yellow());
+            expected
+        },
+        Grade::Wrong { expected } => {
+            println!("\n{}", "INCORRECT".red());
+            expected
+        },
+        Grade::Exact => return,
+    };
+
+    match command.kind {
+        CardKind::Recall | CardKind::Cloze => {},
+        // Each failed step is shown with its closest command
+        CardKind::Scenario => {
+            println!("Expected: {}", expected.green());
+            print_diff(answer, expected);
+            return;
+        },
+        CardKind::MultipleChoice | CardKind::Reverse => {
+            println!("Expected: {}", expected.green());
+            return;
+        },
+        // The explanation was shown before the user graded it
+        CardKind::Explain => return,
+    }
+
+    println!("Expected:");
+    command.commands.iter().for_each(|c| println!("{}", c.green()));
+    print_diff(answer, expected);
+}
+
+fn print_diff(answer: &str, expected: &str) {
+    let diff: String = diff(answer, expected).iter().map(|part| match part {
+        DiffPart::Same(text) => text.normal().to_string(),
+        DiffPart::Extra(text) => text.red().strikethrough().to_string(),
+        DiffPart::Missing(text) => text.green().underline().to_string(),
+    }).collect();
+    println!("Diff: {}", diff);
+}
+
+enum Asked {
+    /// `retried` is set when the user tried again after a near miss, and
+    /// `failed_steps` has the tasks of the steps of a scenario answered wrong
+    Answered { answer: String, grade: Grade, retried: bool, failed_steps: Vec<String> },
+    Undo,
+    Quit,
+}
+
+fn print_question(command: &Command, progress: &str) {
+    let (label, question) = cards::question(command);
+    println!("{} {} \n{}: {}\n", 
+        "RECLI".magenta().bold(),
+        progress,
+        label,
+        question.cyan()
+    );
+
+    if let Some(context) = &command.context {
+        println!("{}", context.trim());
+    }
+}
 
-        if let Some(clues) = &command.clues {
-            println!("{}\n", clues.trim().cyan());
+/// Reads the answer to `command` in the way of its kind and grades it.
+/// `options` are the choices of multiple choice and reverse cards.
+fn ask(answer_prompt: &mut AnswerPrompt, command: &Command, options: &[String], hints: &mut usize) -> anyhow::Result<Asked> {
+    // Hints are only given for typed answers
+    let read = |answer_prompt: &mut AnswerPrompt, prompt: &str| -> anyhow::Result<Result<String, Asked>> {
+        loop {
+            match answer_prompt.read_answer(prompt, None, "")? {
+                Answer::Line(answer) => return Ok(Ok(answer)),
+                Answer::Hint(_) => continue,
+                Answer::Undo => return Ok(Err(Asked::Undo)),
+                Answer::Quit => return Ok(Err(Asked::Quit)),
+            }
         }
+    };
 
-        if let Some(context) = &command.context {
-            println!("{}", context.trim());
+    match command.kind {
+        CardKind::Recall | CardKind::Cloze => {
+            let prompt = match &command.prompt {
+                Some(prompt) => format!("{} ", prompt.trim()),
+                None => String::from("> ")
+            };
+            let mut retried = false;
+
+            loop {
+                let answer = match read_answer_with_hints(answer_prompt, &prompt, command, hints)? {
+                    Answer::Line(answer) => answer,
+                    Answer::Hint(_) => unreachable!(),
+                    Answer::Undo => return Ok(Asked::Undo),
+                    Answer::Quit => return Ok(Asked::Quit),
+                };
+
+                let grade = grade(&answer, &command.commands);
+                if let (Grade::NearMiss { .. }, false) = (&grade, retried) {
+                    println!("{}", "ALMOST! There is a typo. Press 'Enter' to try again or 'n' to see the answer.".yellow());
+                    let Some(user_input) = answer_prompt.read_line("")? else {
+                        return Ok(Asked::Quit);
+                    };
+
+                    if user_input.trim() != "n" {
+                        retried = true;
+                        continue;
+                    }
+                }
+
+                return Ok(Asked::Answered { answer, grade, retried, failed_steps: vec![] });
+            }
+        },
+        CardKind::MultipleChoice | CardKind::Reverse => {
+            for (number, option) in options.iter().enumerate() {
+                println!("{}) {}", number + 1, option);
+            }
+
+            let prompt = if options.is_empty() { "> ".to_string() } else { format!("[1-{}] ", options.len()) };
+            let answer = match read(answer_prompt, &prompt)? {
+                Ok(answer) => answer,
+                Err(asked) => return Ok(asked),
+            };
+            let grade = cards::grade_choice(command, options, &answer);
+
+            Ok(Asked::Answered { answer, grade, retried: false, failed_steps: vec![] })
+        },
+        CardKind::Explain => {
+            let answer = match read(answer_prompt, "> ")? {
+                Ok(answer) => answer,
+                Err(asked) => return Ok(asked),
+            };
+
+            println!("\nExpected:\n{}\n", cards::expected_answer(command).green());
+            println!("Did you explain it correctly? (y/n)");
+
+            let assessment = match read(answer_prompt, "[y/n] ")? {
+                Ok(assessment) => assessment,
+                Err(asked) => return Ok(asked),
+            };
+            let grade = if assessment.trim().eq_ignore_ascii_case("y") {
+                Grade::Exact
+            }
+            else {
+                Grade::Wrong { expected: cards::expected_answer(command) }
+            };
+
+            Ok(Asked::Answered { answer, grade, retried: false, failed_steps: vec![] })
+        },
+        CardKind::Scenario => {
+            let mut answers = vec![];
+            let mut failed_steps = vec![];
+
+            for (index, step) in command.steps.iter().enumerate() {
+                println!("{} {}", format!("Step {}/{}:", index + 1, command.steps.len()).bold(), step.task.cyan());
+
+                let answer = match read(answer_prompt, "> ")? {
+                    Ok(answer) => answer,
+                    Err(asked) => return Ok(asked),
+                };
+
+                let grade = grade(&answer, &step.commands);
+                if grade.is_correct() {
+                    if let Some(response) = &step.response {
+                        println!("{}", response.trim());
+                    }
+                }
+                else {
+                    print_incorrect(command, &answer, &grade);
+                    failed_steps.push(step.task.clone());
+                }
+                println!();
+                answers.push(answer);
+            }
+
+            let grade = if failed_steps.is_empty() { Grade::Exact } else { Grade::Wrong { expected: String::new() } };
+            Ok(Asked::Answered { answer: answers.join("\n"), grade, retried: false, failed_steps })
+        },
+    }
+}
+
+/// Shows how many steps of a scenario were correct and which ones failed.
+fn print_steps_summary(command: &Command, failed_steps: &[String]) {
+    let correct = command.steps.len() - failed_steps.len();
+    println!("{} of {} steps correct", correct, command.steps.len());
+
+    for step in &command.steps {
+        if failed_steps.contains(&step.task) {
+            println!("{} {}", "✗".red(), step.task.red().bold());
+        }
+        else {
+            println!("{} {}", "✓".green(), step.task);
+        }
+    }
+}
+
+/// Reads the answer to `command`, showing the next hint of its ladder each
+/// time the user presses Ctrl+T. `hints` counts the hints shown.
+fn read_answer_with_hints(answer_prompt: &mut AnswerPrompt, prompt: &str, command: &Command, hints: &mut usize) -> anyhow::Result<Answer> {
+    let ladder = hint_ladder(command);
+    let mut typed = String::new();
+
+    println!("{}", format!("Ctrl+T: hint ({} available)", ladder.len()).dimmed());
+
+    loop {
+        match answer_prompt.read_answer(prompt, command.fixtures.as_deref(), &typed)? {
+            Answer::Hint(line) => {
+                match ladder.get(*hints) {
+                    Some(hint) => {
+                        println!("{}", hint.cyan());
+                        *hints += 1;
+                    },
+                    None => println!("{}", "There are no more hints.".yellow()),
+                }
+                typed = line;
+            },
+            answer => return Ok(answer),
         }
+    }
+}
+
+/// Records the evaluation of a reviewed command and saves its new schedule.
+/// `response_time` is the time the user took to answer, `hints` the number
+/// of hints shown and `failed_steps` the failed steps of a scenario.
+pub async fn rate_command(command: &mut Command, evaluation: &Evaluation, response_time: Duration, hints: usize, failed_steps: &[String]) -> anyhow::Result<()> {
+    database::save_rating_snapshot(command).await?;
+    let before = command.sr_data.clone();
+
+    command.sr_data.review_count += 1;
+    command.sr_data.last_review = Some(get_current_date());
+    command.sr_data = compute_sr_data(&command.sr_data, evaluation, true);
+
+    database::update_command(command).await?;
+    database::save_review_log(command, &before, evaluation, response_time, hints, failed_steps).await
+}
+
+/// Puts `current` back in the queue and, in front of it, the last command
+/// rated today with the schedule it had before that rating. A failed rating
+/// is undone with the requeue it caused. Returns `false` if there was no
+/// rating to undo.
+pub async fn undo_last_rating(session: &mut ReviewSession, commands: &mut VecDeque<Command>, current: Command) -> anyhow::Result<bool> {
+    commands.push_front(current);
+
+    let Some(command) = database::undo_last_rating().await? else { return Ok(false) };
 
-        match &command.prompt {
-            Some(prompt) => print!("{} ", prompt.trim()),
-            None => print!("> ")
+    // A command is only queued again after it was failed
+    let requeued = commands.iter().any(|queued| queued.id == command.id);
+    commands.retain(|queued| queued.id != command.id);
+    if let (true, Some(id)) = (requeued, command.id) {
+        // Unless it was failed before in the session
+        if !database::find_failed_since(session.started).await?.contains(&id) {
+            session.requeued.retain(|requeued| *requeued != id);
         }
+    }
+    commands.push_front(command);
+    session.reviewed = (session.reviewed - 1).max(0);
+    save_review(session, commands).await?;
 
-        user_input = String::from("");
-        io::stdout().flush().unwrap();
-        io::stdin().read_line(&mut user_input).unwrap();
+    Ok(true)
+}
 
-        let is_correct = command.commands.contains(&user_input.trim().to_string());
+/// Rates `command`, taken from the front of the queue, and saves the
+/// session in the same step: a failed command goes back to the end of the
+/// queue, any other leaves it, so that a resumed review doesn't ask it again.
+pub async fn rate_reviewed(session: &mut ReviewSession, commands: &mut VecDeque<Command>, mut command: Command, evaluation: &Evaluation, response_time: Duration, hints: usize, failed_steps: &[String]) -> anyhow::Result<()> {
+    rate_command(&mut command, evaluation, response_time, hints, failed_steps).await?;
+    session.reviewed += 1;
+
+    if evaluation.get_num() < 3 {
+        session.requeue(&command);
+        commands.push_back(command);
+    }
+
+    save_review(session, commands).await
+}
+
+/// Saves the session with `current`, the command being asked, back in front
+/// of the queue and shows what was reviewed so far.
+async fn stop_review(session: &mut ReviewSession, commands: &mut VecDeque<Command>, current: Option<Command>) -> anyhow::Result<()> {
+    if let Some(command) = current {
+        commands.push_front(command);
+    }
+    save_review(session, commands).await?;
+
+    println!();
+    session.print_summary(commands.len());
+    show_review_report(session).await
+}
+
+pub async fn review(session: &mut ReviewSession, commands: &mut VecDeque<Command>, prompt_options: &PromptOptions) -> anyhow::Result<()> {
+    let mut answer_prompt = AnswerPrompt::new(prompt_options)?;
+    save_review(session, commands).await?;
+    handle_interrupts()?;
+
+    while !commands.is_empty() {
+        if is_interrupted() {
+            return stop_review(session, commands, None).await;
+        }
+        clear();
+        let progress = session.progress(commands);
+
+        let command: Command = commands.pop_front().unwrap();
+        let options = cards::find_options(&command).await?;
+        print_question(&command, &progress);
+
+        let started = Instant::now();
+        let mut hints = 0;
+        let (answer, grade, retried, failed_steps) = match ask(&mut answer_prompt, &command, &options, &mut hints)? {
+            Asked::Answered { answer, grade, retried, failed_steps } => (answer, grade, retried, failed_steps),
+            Asked::Undo => {
+                if !undo_last_rating(session, commands, command).await? {
+                    println!("{}", "There is no rating to undo today.".yellow());
+                    answer_prompt.read_line("")?;
+                }
+                continue;
+            },
+            Asked::Quit => return stop_review(session, commands, Some(command)).await,
+        };
+
+        let is_correct = grade.is_correct();
+        let response_time = started.elapsed();
+        // Retrying after a typo caps the rating as much as two hints
+        let penalty = if retried { hints + 2 } else { hints };
         let user_eval;
 
-        if let Mode::Learning = command.sr_data.mode {
+        let mut suggestion = None;
+        if is_correct && command.kind.is_typed() {
+            let (command_times, user_times) = database::find_response_times(&command).await?;
+            if let Some(baseline) = response_baseline(&command_times, &user_times, answer.trim().chars().count()) {
+                suggestion = Some((suggest_evaluation(response_time, baseline).capped(penalty), baseline));
+            }
+            database::save_response_time(&command, answer.trim(), response_time).await?;
+        }
+
+        if command.kind == CardKind::Scenario {
+            // Scenarios are evaluated by how many of their steps were correct
+            print_steps_summary(&command, &failed_steps);
+            user_eval = Evaluation::from_steps(command.steps.len() - failed_steps.len(), command.steps.len());
+
+            if let Some(extra) = &command.extra {
+                println!("\n{}", extra.trim().cyan());
+            }
+            println!("{}", "Press 'Enter' to continue.".cyan());
+            if answer_prompt.read_line("")?.is_none() {
+                return stop_review(session, commands, Some(command)).await;
+            }
+        }
+        else if let Mode::Learning = command.sr_data.mode {
             if is_correct {
-                if let Some(response) = &command.response {
+                if let Some(response) = command.response.as_ref().filter(|_| command.kind != CardKind::Explain) {
                     println!("{}", response.trim());
                 }
 
@@ -139,10 +626,28 @@ pub async fn review(commands: &mut VecDeque<Command>) -> anyhow::Result<()> {
                 let perfect_e_factor_increase = format!("+{}%", (current_e_factor - (eval3.e_factor * 100.0)).abs().floor());
 
                 println!("\n{}", "CORRECT!".green());
-                println!("Ease: {}", format!("{}%", current_e_factor.to_string()).cyan());
+                println!("Ease: {}", format!("{}%", current_e_factor).cyan());
                 println!("1: Hard       {} days  {}", eval1.interval, hard_e_factor_decrease.red());
                 println!("2: Good       {} days", eval2.interval);
                 println!("3: Perfect    {} days  {}", eval3.interval, perfect_e_factor_increase.green());
+                println!("u: Undo the previous rating");
+
+                if let Some((evaluation, baseline)) = &suggestion {
+                    let choice = Evaluation::choices(true).iter().position(|choice| choice.get_num() == evaluation.get_num()).unwrap() + 1;
+                    println!("\nAnswered in {:.1}s, usually {:.1}s. Press Enter to choose {}.",
+                        response_time.as_secs_f64(),
+                        baseline.as_secs_f64(),
+                        choice.to_string().cyan()
+                    );
+                }
+
+                if retried {
+                    println!("{}", "Answered after a retry, the rating is at most Hard.".yellow());
+                }
+                else if hints > 0 {
+                    let cap = if hints == 1 { "Good" } else { "Hard" };
+                    println!("{}", format!("{} hints used, the rating is at most {}.", hints, cap).yellow());
+                }
             }
             else {
                 if let Some(extra) = &command.extra {
@@ -157,21 +662,35 @@ pub async fn review(commands: &mut VecDeque<Command>) -> anyhow::Result<()> {
                 let blackout_e_factor_decrease = format!("-{}%",(current_e_factor - (eval1.e_factor * 100.0)).abs().floor());
                 let remembered_e_factor_decrease = format!("-{}%",(current_e_factor - (eval2.e_factor * 100.0)).abs().floor());
                 let easy_e_factor_decrease = format!("-{}%", (current_e_factor - (eval3.e_factor * 100.0)).abs().floor());
-                println!("\n{}", "INCORRECT".red());
-                println!("Expected:");
-                command.commands.iter().for_each(|c| println!("{}", c.green()));
+                print_incorrect(&command, &answer, &grade);
                 
                 println!("{}", "Command scheduled for tomorrow".yellow());
-                println!("Ease: {}", format!("{}%", current_e_factor.to_string()).cyan());
+                println!("Ease: {}", format!("{}%", current_e_factor).cyan());
                 println!("1: Complete blackout  {}", blackout_e_factor_decrease.red());
                 println!("2: Remembered         {}", remembered_e_factor_decrease.red());
                 println!("3: Easy recall        {}", easy_e_factor_decrease.red());
+                println!("u: Undo the previous rating");
+
+                if let Grade::NearMiss { .. } = grade {
+                    println!("\nIt was only a typo. Press Enter to choose {}.", "3".cyan());
+                    suggestion = Some((Evaluation::IncorrectWithEasyRecall, Duration::ZERO));
+                }
             }
-            user_eval = get_eval_from_user(is_correct);
+            user_eval = match get_eval_from_user(&mut answer_prompt, is_correct, suggestion.as_ref().map(|(evaluation, _)| evaluation))? {
+                Rating::Rated(evaluation) => evaluation.capped(penalty),
+                Rating::Undo => {
+                    if !undo_last_rating(session, commands, command).await? {
+                        println!("{}", "There is no rating to undo today.".yellow());
+                        answer_prompt.read_line("")?;
+                    }
+                    continue;
+                },
+                Rating::Quit => return stop_review(session, commands, Some(command)).await,
+            };
         }
         else { // If command is new or failed evalutate automatically
             if is_correct {
-                if let Some(response) = &command.response {
+                if let Some(response) = command.response.as_ref().filter(|_| command.kind != CardKind::Explain) {
                     println!("{}", response);
                 }
 
@@ -187,56 +706,409 @@ pub async fn review(commands: &mut VecDeque<Command>) -> anyhow::Result<()> {
                     println!("\n{}", extra.trim().cyan());
                 }
 
-                println!("\n{}", "INCORRECT".red());
-                println!("Expected:");
-                command.commands.iter().for_each(|c| println!("{}", c.green()));
-                
-                user_eval = Evaluation::Blackout;
+                print_incorrect(&command, &answer, &grade);
+
+                user_eval = match grade {
+                    Grade::NearMiss { .. } => Evaluation::IncorrectWithEasyRecall,
+                    _ => Evaluation::Blackout,
+                };
             }
             println!("{}", "Press 'Enter' to continue.".cyan());
-            io::stdin().read_line(&mut user_input).unwrap();
+            if answer_prompt.read_line("")?.is_none() {
+                return stop_review(session, commands, Some(command)).await;
+            }
+        }
+
+        rate_reviewed(session, commands, command, &user_eval, response_time, hints, &failed_steps).await?;
+    }
+    clear();
+    println!("RECLI: No more commands to review today.");
+    show_review_report(session).await?;
+
+    Ok(())
+}
+
+/// Reviews `commands` in reverse: the command is shown and the user explains
+/// what it does, then grades the explanation against the task. The ratings
+/// only change the schedule of the reverse direction.
+pub async fn review_reverse(commands: &mut VecDeque<Command>, prompt_options: &PromptOptions) -> anyhow::Result<()> {
+    let mut answer_prompt = AnswerPrompt::new(prompt_options)?;
+    let original_size = commands.len();
+    let mut results: Vec<ReviewResult> = vec![];
+
+    while !commands.is_empty() {
+        clear();
+        let progress = format!("{}/{}", original_size - commands.len(), original_size);
+        let mut command: Command = commands.pop_front().unwrap();
+
+        println!("{} {} \nWhat does this command do? {}\n",
+            "RECLI".magenta().bold(),
+            progress,
+            command.commands.first().map(|command| command.trim()).unwrap_or_default().cyan()
+        );
+        if let Some(context) = &command.context {
+            println!("{}", context.trim());
+        }
+
+        let started = Instant::now();
+        let answer = loop {
+            match answer_prompt.read_answer("> ", None, "")? {
+                Answer::Line(answer) => break Some(answer),
+                Answer::Quit => break None,
+                // Reverse ratings can't be undone and there are no hints
+                Answer::Hint(_) | Answer::Undo => continue,
+            }
+        };
+        if answer.is_none() {
+            commands.push_front(command);
+            break;
+        }
+        let response_time = started.elapsed();
+
+        println!("\nExpected:\n{}", command.task.green());
+        if let Some(extra) = &command.extra {
+            println!("\n{}", extra.trim().cyan());
         }
+        println!("\nDid you explain it correctly? (y/n)");
 
+        let Some(user_input) = answer_prompt.read_line("")? else {
+            commands.push_front(command);
+            break;
+        };
+        let is_correct = user_input.trim().eq_ignore_ascii_case("y");
+
+        let user_eval = if let Mode::Learning = command.sr_data.mode {
+            let labels = if is_correct { ["Hard", "Good", "Perfect"] } else { ["Complete blackout", "Remembered", "Easy recall"] };
+            for (number, (label, evaluation)) in labels.iter().zip(Evaluation::choices(is_correct)).enumerate() {
+                let sr_data = compute_sr_data(&command.sr_data, &evaluation, false);
+                println!("{}: {:<18} {} days", number + 1, label, sr_data.interval);
+            }
+
+            let rating = loop {
+                match get_eval_from_user(&mut answer_prompt, is_correct, None)? {
+                    // Reverse ratings can't be undone
+                    Rating::Undo => continue,
+                    rating => break rating,
+                }
+            };
+            match rating {
+                Rating::Rated(evaluation) => evaluation,
+                _ => {
+                    commands.push_front(command);
+                    break;
+                },
+            }
+        }
+        else if is_correct {
+            Evaluation::CorrectButHard
+        }
+        else {
+            Evaluation::Blackout
+        };
+
+        let e_factor_before = command.sr_data.e_factor;
+        let mode_before = command.sr_data.mode.clone();
         command.sr_data.review_count += 1;
         command.sr_data.last_review = Some(get_current_date());
         command.sr_data = compute_sr_data(&command.sr_data, &user_eval, true);
+        database::update_reverse_progress(&command).await?;
 
-        database::update_command(&command).await?;
+        results.push(ReviewResult {
+            command_id: command.id.unwrap_or(0),
+            reviewed: chrono::Utc::now(),
+            lapsed: mode_before == Mode::Learning && command.sr_data.mode == Mode::Failed,
+            mode_before,
+            task: command.task.clone(),
+            tags: command.tags.clone().unwrap_or_default(),
+            is_correct: user_eval.get_num() >= 3,
+            response_time: Some(response_time),
+            hints: 0,
+            e_factor_before: Some(e_factor_before),
+            e_factor_after: Some(command.sr_data.e_factor),
+            failed_steps: vec![],
+        });
 
         if user_eval.get_num() < 3 {
             commands.push_back(command);
         }
-
     }
+
     clear();
-    println!("RECLI: No more commands to review today.");
+    if commands.is_empty() {
+        println!("RECLI: No more commands to review in reverse today.");
+    }
+    show_report(&results, None);
 
     Ok(())
 }
 
-pub fn show_commands(commands: &Vec<Command>) {
+/// Imports every deck file matched by `path`, reporting the result of each
+/// file and continuing past the ones that can't be read.
+pub async fn import(path: &str, format: Option<&ImportFormat>, dry_run: bool) -> anyhow::Result<()> {
+    let files = find_deck_files(path, format)?;
+    let mut preview: Vec<Command> = vec![];
+    let mut failed = 0;
+
+    for file in &files {
+        if let Some(error) = &file.error {
+            println!("{}", format!("{}: {}", file.path, error).red());
+            failed += 1;
+            continue;
+        }
+
+        let (deck, mut commands) = match get_commands_from_file(&file.path, format) {
+            Ok(commands_vec) => (commands_vec.deck, commands_vec.commands),
+            Err(error) => {
+                println!("{}", error.to_string().red());
+                failed += 1;
+                continue;
+            }
+        };
+
+        if let Some(tag) = &file.tag {
+            for command in commands.iter_mut() {
+                let tags = command.tags.get_or_insert_with(Vec::new);
+                if !tags.contains(tag) {
+                    tags.push(tag.clone());
+                }
+            }
+        }
+
+        if let Some(Deck { name: Some(name), version, .. }) = &deck {
+            match version {
+                Some(version) => println!("{} {}", name.cyan(), version),
+                None => println!("{}", name.cyan()),
+            }
+        }
+
+        // Counted as they are saved, with a card per gap of the cloze cards
+        let commands: Vec<Command> = commands.iter().flat_map(cards::cloze_siblings).collect();
+
+        if dry_run {
+            println!("{}: {} commands", file.path, commands.len());
+            preview.extend(commands);
+        }
+        else {
+            let saved = database::save_commands(&commands).await?;
+            println!("{}: {} commands, {} new", file.path, commands.len(), saved);
+        }
+    }
+
+    if dry_run {
+        show_import_preview(&preview);
+    }
+
+    if failed > 0 {
+        println!("{}", format!("{} of {} files could not be imported", failed, files.len()).yellow());
+    }
+
+    if files.is_empty() {
+        println!("No deck files found in {}", path);
+    }
+
+    Ok(())
+}
+
+/// Shows the report of the reviews made since the session started, and how
+/// many commands are due tomorrow.
+pub async fn show_review_report(session: &ReviewSession) -> anyhow::Result<()> {
+    let results = database::find_review_log(session.started).await?;
+    let due_tomorrow = database::count_due_on(get_current_date() + chrono::Duration::days(1)).await?;
+
+    show_report(&results, Some(due_tomorrow));
+    Ok(())
+}
+
+/// Shows accuracy, response times and ease changes for a list of answers,
+/// in total and per tag.
+pub fn show_report(results: &[ReviewResult], due_tomorrow: Option<usize>) {
+    if results.is_empty() { return; }
+
+    let commands: HashSet<i64> = results.iter().map(|result| result.command_id).collect();
+    let correct = results.iter().filter(|result| result.is_correct).count();
+    let response_times: Vec<Duration> = results.iter().filter_map(|result| result.response_time).collect();
+
+    let mut table = new_table(vec!["Session", ""]);
+    table.add_row(vec![Cell::new("Commands reviewed"), Cell::new(format!("{} ({} answers)", commands.len(), results.len()))]);
+    table.add_row(vec![Cell::new("Accuracy"), Cell::new(percentage(correct, results.len()))]);
+
+    if !response_times.is_empty() {
+        let average = response_times.iter().sum::<Duration>() / response_times.len() as u32;
+        table.add_row(vec![Cell::new("Average response time"), Cell::new(format!("{:.1}s", average.as_secs_f64()))]);
+    }
+
+    let hints: usize = results.iter().map(|result| result.hints).sum();
+    if hints > 0 {
+        let answers = results.iter().filter(|result| result.hints > 0).count();
+        table.add_row(vec![Cell::new("Hints used"), Cell::new(format!("{} in {} answers", hints, answers))]);
+    }
+
+    let ease_changes: Vec<f64> = results.iter()
+        .filter_map(|result| Some(result.e_factor_after? - result.e_factor_before?))
+        .collect();
+    if !ease_changes.is_empty() {
+        let increased = ease_changes.iter().filter(|change| **change > 0.0).count();
+        let decreased = ease_changes.iter().filter(|change| **change < 0.0).count();
+        let average = ease_changes.iter().sum::<f64>() / ease_changes.len() as f64 * 100.0;
+        table.add_row(vec![
+            Cell::new("Ease changes"),
+            Cell::new(format!("{} up, {} down ({:+.0}% on average)", increased, decreased, average)),
+        ]);
+    }
+
+    let mut lapsed: Vec<&str> = results.iter()
+        .filter(|result| result.lapsed)
+        .map(|result| result.task.as_str())
+        .collect();
+    lapsed.sort();
+    lapsed.dedup();
+    if !lapsed.is_empty() {
+        table.add_row(vec![Cell::new("Lapsed"), Cell::new(lapsed.join("\n")).fg(Color::Red)]);
+    }
+
+    let failed_steps: Vec<String> = results.iter()
+        .flat_map(|result| result.failed_steps.iter().map(|step| format!("{}: {}", result.task, step)))
+        .collect();
+    if !failed_steps.is_empty() {
+        table.add_row(vec![Cell::new("Failed steps"), Cell::new(failed_steps.join("\n")).fg(Color::Red)]);
+    }
+
+    if let Some(due_tomorrow) = due_tomorrow {
+        table.add_row(vec![Cell::new("Due tomorrow"), Cell::new(due_tomorrow)]);
+    }
+    println!("{table}");
+
+    let mut tags: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for result in results {
+        let mut result_tags: Vec<&str> = result.tags.iter().map(|tag| tag.as_str()).collect();
+        if result_tags.is_empty() {
+            result_tags.push("(no tag)");
+        }
+
+        for tag in result_tags {
+            let (answers, correct) = tags.entry(tag).or_default();
+            *answers += 1;
+            if result.is_correct { *correct += 1; }
+        }
+    }
+
+    let mut table = new_table(vec!["Tag", "Answers", "Correct", "Accuracy"]);
+    for (tag, (answers, correct)) in tags {
+        table.add_row(vec![
+            Cell::new(tag).fg(Color::Cyan),
+            Cell::new(answers),
+            Cell::new(correct),
+            Cell::new(percentage(correct, answers)),
+        ]);
+    }
+    println!("{table}");
+}
+
+fn percentage(part: usize, total: usize) -> String {
+    format!("{:.0}%", part as f64 / total as f64 * 100.0)
+}
+
+pub fn new_table(header: Vec<&str>) -> Table {
     let mut table = Table::new();
     table.load_preset(UTF8_FULL)
         .set_content_arrangement(ContentArrangement::Dynamic)
         .set_width(80)
-        .set_header(vec![
-            Cell::new("Id"),
-            Cell::new("Task"),
-            Cell::new("Command"),
-            Cell::new("Ease"),
-            Cell::new("Interval"),
-        ]);
+        .set_header(header.into_iter().map(Cell::new).collect::<Vec<Cell>>());
+    table
+}
+
+pub fn show_commands(commands: &Vec<Command>) {
+    let mut table = new_table(vec!["Id", "Task", "Command", "Ease", "Interval"]);
     
     for command in commands {
         let ease = (command.sr_data.e_factor*100.0).floor();
         table.add_row(vec![
              Cell::new(command.id.unwrap_or(0)),
              Cell::new(&command.task).fg(Color::Cyan),
-             Cell::new(&command.commands.get(0).unwrap_or(&"".to_string())),
+             Cell::new(command.commands.first().unwrap_or(&"".to_string())),
              Cell::new(ease),
-             Cell::new(&command.sr_data.interval),
+             Cell::new(command.sr_data.interval),
         ]);
     }
     println!("Showing {} commands", commands.len());
     println!("{table}");
-}
\ No newline at end of file
+}
+
+/// Shows the commands found by a search with the matched words highlighted.
+pub fn show_search_results(results: &[(Command, String)]) {
+    let mut table = new_table(vec!["Id", "Task", "Match", "Tags"]);
+
+    for (command, snippet) in results {
+        let mut highlighted = String::new();
+        for (index, part) in snippet.split(['\u{1}', '\u{2}']).enumerate() {
+            // The parts between the markers are the matches
+            match index % 2 {
+                1 => highlighted.push_str(&part.yellow().bold().to_string()),
+                _ => highlighted.push_str(part),
+            }
+        }
+
+        table.add_row(vec![
+             Cell::new(command.id.unwrap_or(0)),
+             Cell::new(&command.task).fg(Color::Cyan),
+             Cell::new(highlighted),
+             Cell::new(command.tags.clone().unwrap_or_default().join(", ")),
+        ]);
+    }
+    println!("Found {} commands", results.len());
+    println!("{table}");
+}
+
+pub fn show_import_preview(commands: &Vec<Command>) {
+    let mut table = new_table(vec!["Task", "Commands", "Tags", "Extra"]);
+
+    for command in commands {
+        table.add_row(vec![
+             Cell::new(&command.task).fg(Color::Cyan),
+             Cell::new(command.commands.join("\n")),
+             Cell::new(command.tags.clone().unwrap_or_default().join(", ")),
+             Cell::new(command.extra.clone().unwrap_or_default()),
+        ]);
+    }
+    println!("{} commands would be imported", commands.len());
+    println!("{table}");
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::SqlitePool;
+
+    #[tokio::test]
+    async fn cram_answers_only_go_to_the_cram_history() {
+        let _database = database::create_test_database().await;
+        let command: Command = toml::from_str("task = \"List the files\"\ncommands = [\"ls\"]").unwrap();
+        database::save_commands(&[command]).await.unwrap();
+        let command = database::find_all_commands().await.unwrap().pop_front().unwrap();
+        let before = command.sr_data.clone();
+
+        let (mut commands, mut streaks) = (VecDeque::new(), HashMap::new());
+        let mut answers = vec![];
+        for is_correct in [true, false, true, true] {
+            let command = commands.pop_front().unwrap_or(command.clone());
+            answers.push(count_drill_answer(&mut commands, &mut streaks, command, is_correct, Duration::ZERO, Some(2)).await.unwrap());
+        }
+
+        // The wrong answer starts the streak again, and the command is done after two in a row
+        assert_eq!(answers, vec![1, 0, 1, 2]);
+        assert!(commands.is_empty());
+
+        let saved = database::find_command(command.id.unwrap()).await.unwrap().unwrap();
+        assert_eq!(saved.sr_data.mode, before.mode);
+        assert_eq!(saved.sr_data.interval, before.interval);
+        assert_eq!(saved.sr_data.e_factor, before.e_factor);
+        assert_eq!(saved.sr_data.last_review, before.last_review);
+
+        let crammed = database::find_cram_log(chrono::Utc::now() - chrono::Duration::days(1)).await.unwrap();
+        assert_eq!(crammed.iter().filter(|result| result.is_correct).count(), 3);
+        assert_eq!(crammed.len(), 4);
+
+        let pool = SqlitePool::connect(&crate::profiles::database_path().to_string_lossy()).await.unwrap();
+        let reviewed: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM review_log").fetch_one(&pool).await.unwrap();
+        assert_eq!(reviewed, 0);
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use rand::seq::SliceRandom;
+use crate::grading::{grade, Grade};
+use crate::tags::is_within;
+use super::database;
+use super::{CardKind, Command};
+
+/// The most options shown by multiple choice and reverse cards.
+const MAX_OPTIONS: usize = 4;
+
+/// The label and the text shown to ask a command, depending on its kind.
+pub fn question(command: &Command) -> (&'static str, String) {
+    match command.kind {
+        CardKind::Recall | CardKind::MultipleChoice => ("Task", command.task.clone()),
+        CardKind::Reverse => ("Command", first_command(command).to_string()),
+        CardKind::Explain => match &command.flag {
+            Some(flag) => ("Explain", format!("{} in {}", flag, first_command(command))),
+            None => ("Explain", first_command(command).to_string()),
+        },
+        CardKind::Cloze => {
+            let cloze = command.cloze.as_deref().unwrap_or_default();
+            ("Task", format!("{}\n{}", command.task, blank_gaps(cloze)))
+        },
+        CardKind::Scenario => ("Scenario", command.task.clone()),
+    }
+}
+
+/// A gap of a cloze, found between `start` and `end` in the text.
+struct Gap {
+    start: usize,
+    end: usize,
+    number: u32,
+    text: String,
+}
+
+/// Finds the gaps marked as `{{c1::text}}`. The text can contain braces, as
+/// in `-exec rm {} +`, as long as they are balanced.
+fn find_gaps(cloze: &str) -> Vec<Gap> {
+    let mut gaps = vec![];
+    let mut search = 0;
+
+    while let Some(offset) = cloze[search..].find("{{c") {
+        let start = search + offset;
+        let rest = &cloze[start + 3..];
+        let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+
+        let (Ok(number), true) = (digits.parse::<u32>(), rest[digits.len()..].starts_with("::")) else {
+            search = start + 3;
+            continue;
+        };
+
+        let text_start = start + 3 + digits.len() + 2;
+        let mut depth = 0;
+        let mut end = None;
+        let mut chars = cloze[text_start..].char_indices().peekable();
+
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth > 0 => depth -= 1,
+                '}' if matches!(chars.peek(), Some((_, '}'))) => {
+                    end = Some(text_start + index);
+                    break;
+                },
+                _ => {},
+            }
+        }
+
+        let Some(text_end) = end else { break };
+        gaps.push(Gap {
+            start,
+            end: text_end + 2,
+            number,
+            text: cloze[text_start..text_end].to_string(),
+        });
+        search = text_end + 2;
+    }
+
+    gaps
+}
+
+/// Replaces the marked gaps with `[...]`.
+fn blank_gaps(cloze: &str) -> String {
+    let mut blanked = cloze.to_string();
+    for gap in find_gaps(cloze).iter().rev() {
+        blanked.replace_range(gap.start..gap.end, "[...]");
+    }
+    blanked
+}
+
+/// Splits a cloze card, whose command or, without commands, `cloze` has its
+/// gaps marked, into a sibling card per gap number. The sibling asks for the
+/// text of its gaps, joined by spaces, and shows the other gaps filled in.
+/// Other commands are returned as they are, so saving the siblings again
+/// doesn't split them.
+pub fn cloze_siblings(command: &Command) -> Vec<Command> {
+    let cloze = match (&command.cloze, command.commands.is_empty()) {
+        (Some(cloze), true) => cloze.trim(),
+        _ => first_command(command),
+    };
+    let gaps = find_gaps(cloze);
+
+    if command.kind != CardKind::Cloze || gaps.is_empty() {
+        return vec![command.clone()];
+    }
+
+    let mut numbers: Vec<u32> = gaps.iter().map(|gap| gap.number).collect();
+    numbers.sort();
+    numbers.dedup();
+
+    numbers.into_iter().map(|number| {
+        // Keeps the gaps of this sibling marked and fills in the others
+        let mut text = cloze.to_string();
+        for gap in gaps.iter().rev().filter(|gap| gap.number != number) {
+            text.replace_range(gap.start..gap.end, &gap.text);
+        }
+
+        let answer: Vec<&str> = gaps.iter()
+            .filter(|gap| gap.number == number)
+            .map(|gap| gap.text.trim())
+            .collect();
+
+        Command {
+            id: None,
+            task: format!("{} [c{}]", command.task, number),
+            commands: vec![answer.join(" ")],
+            cloze: Some(text),
+            // The siblings of a cloze are siblings for burying too
+            group: command.group.clone().or(Some(command.task.clone())),
+            ..command.clone()
+        }
+    }).collect()
+}
+
+/// Leaves only the first of each set of sibling commands in the queue, so
+/// that the others are reviewed on the next days. Commands are siblings when
+/// they are in the same group or their answers start with the same tokens.
+/// `rules` has, for each tag that buries siblings, how many tokens are
+/// compared. Commands without such a tag are never buried.
+pub fn bury_siblings(commands: VecDeque<Command>, rules: &HashMap<String, usize>) -> VecDeque<Command> {
+    let mut seen: HashSet<String> = HashSet::new();
+
+    commands.into_iter().filter(|command| {
+        let keys = sibling_keys(command, rules);
+        let is_sibling = keys.iter().any(|key| seen.contains(key));
+        seen.extend(keys);
+        !is_sibling
+    }).collect()
+}
+
+fn sibling_keys(command: &Command, rules: &HashMap<String, usize>) -> Vec<String> {
+    let tokens: Vec<usize> = rules.iter()
+        .filter(|(rule_tag, _)| command.tags.iter().flatten().any(|tag| is_within(tag, rule_tag)))
+        .map(|(_, tokens)| *tokens)
+        .collect();
+    if tokens.is_empty() {
+        return vec![];
+    }
+
+    let mut keys = vec![];
+    if let Some(group) = &command.group {
+        keys.push(format!("group:{}", group));
+    }
+
+    let answer: Vec<&str> = first_command(command).split_whitespace().collect();
+    for count in tokens {
+        if count > 0 && answer.len() >= count {
+            keys.push(format!("tokens:{}", answer[..count].join(" ")));
+        }
+    }
+
+    keys
+}
+
+/// The answer of a command that is not typed: the task of reverse cards,
+/// the explanation of explain cards and the first command otherwise.
+pub fn expected_answer(command: &Command) -> String {
+    match command.kind {
+        CardKind::Reverse => command.task.clone(),
+        CardKind::Explain => command.response.clone().or(command.extra.clone()).unwrap_or_default().trim().to_string(),
+        _ => first_command(command).to_string(),
+    }
+}
+
+/// The shuffled options of multiple choice and reverse cards: the expected
+/// answer and, as distractors, the answers of other commands sharing a tag.
+/// Other kinds of cards have no options.
+pub async fn find_options(command: &Command) -> anyhow::Result<Vec<String>> {
+    if !matches!(command.kind, CardKind::MultipleChoice | CardKind::Reverse) {
+        return Ok(vec![]);
+    }
+
+    let expected = expected_answer(command);
+    let mut distractors: Vec<String> = vec![];
+
+    for tag in command.tags.iter().flatten() {
+        for other in database::find_commands_with_tag(tag).await? {
+            if other.id == command.id { continue; }
+
+            let answer = match command.kind {
+                CardKind::Reverse => other.task.clone(),
+                _ => first_command(&other).to_string(),
+            };
+            if answer != expected && !command.commands.contains(&answer) && !distractors.contains(&answer) {
+                distractors.push(answer);
+            }
+        }
+    }
+
+    let mut rng = rand::thread_rng();
+    distractors.shuffle(&mut rng);
+    distractors.truncate(MAX_OPTIONS - 1);
+
+    if distractors.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut options = distractors;
+    options.push(expected);
+    options.shuffle(&mut rng);
+
+    Ok(options)
+}
+
+/// Grades the answer to a multiple choice or reverse card, given as the
+/// number of an option or as text. Without options, the text is graded
+/// against the expected answer as a typed command would be.
+pub fn grade_choice(command: &Command, options: &[String], answer: &str) -> Grade {
+    let expected = expected_answer(command);
+
+    let chosen = answer.trim().parse::<usize>().ok()
+        .and_then(|number| options.get(number.wrapping_sub(1)))
+        .map(|option| option.as_str())
+        .unwrap_or(answer.trim());
+
+    match grade(chosen, std::slice::from_ref(&expected)) {
+        Grade::NearMiss { .. } if !options.is_empty() => Grade::Wrong { expected },
+        grade => grade,
+    }
+}
+
+fn first_command(command: &Command) -> &str {
+    command.commands.first().map(|command| command.trim()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_cloze_number_is_a_sibling_card() {
+        let command: Command = toml::from_str(r#"
+            task = "Delete the old logs"
+            commands = ["find . -name '*.log' {{c1::-mtime +7}} {{c2::-exec rm {} +}}"]
+            type = "cloze"
+        "#).unwrap();
+
+        let siblings = cloze_siblings(&command);
+
+        assert_eq!(siblings.len(), 2);
+        assert_eq!(siblings[0].task, "Delete the old logs [c1]");
+        assert_eq!(siblings[0].commands, vec!["-mtime +7"]);
+        assert_eq!(siblings[1].commands, vec!["-exec rm {} +"]);
+        assert_eq!(question(&siblings[1]).1, "Delete the old logs [c2]\nfind . -name '*.log' -mtime +7 [...]");
+        assert_eq!(cloze_siblings(&siblings[0]).len(), 1);
+    }
+
+    #[test]
+    fn the_gaps_can_be_marked_in_the_cloze_field() {
+        let command: Command = toml::from_str(r#"
+            task = "Follow the logs"
+            cloze = "tail {{c1::-f}} app.log"
+            type = "cloze"
+        "#).unwrap();
+
+        let siblings = cloze_siblings(&command);
+
+        assert_eq!(siblings.len(), 1);
+        assert_eq!(siblings[0].commands, vec!["-f"]);
+        // The sibling is kept as it is when it is imported again
+        let imported = cloze_siblings(&siblings[0]);
+        assert_eq!(imported[0].task, siblings[0].task);
+        assert_eq!(imported[0].cloze, siblings[0].cloze);
+    }
+
+    #[test]
+    fn siblings_are_buried_only_for_tags_with_a_rule() {
+        let command = |task: &str, answer: &str| -> Command {
+            toml::from_str(&format!("task = \"{}\"\ncommands = [\"{}\"]\ntags = [\"git\"]", task, answer)).unwrap()
+        };
+        let commands: VecDeque<Command> = vec![
+            command("Stash the changes", "git stash"),
+            command("Restore the stashed changes", "git stash pop"),
+            command("Show the working tree status", "git status"),
+        ].into();
+
+        let rules = HashMap::from([("git".to_string(), 2)]);
+        let tasks: Vec<String> = bury_siblings(commands.clone(), &rules).into_iter().map(|command| command.task).collect();
+
+        assert_eq!(tasks, vec!["Stash the changes", "Show the working tree status"]);
+        assert_eq!(bury_siblings(commands, &HashMap::new()).len(), 3);
+    }
+
+    #[test]
+    fn choices_are_graded_by_number_or_text() {
+        let command: Command = toml::from_str(r#"
+            task = "Show the working tree status"
+            commands = ["git status"]
+            type = "multiple-choice"
+        "#).unwrap();
+        let options = vec!["git log".to_string(), "git status".to_string()];
+
+        assert_eq!(command.kind, CardKind::MultipleChoice);
+        assert_eq!(grade_choice(&command, &options, "2"), Grade::Exact);
+        assert_eq!(grade_choice(&command, &options, "git status"), Grade::Exact);
+        assert_eq!(grade_choice(&command, &options, "1"), Grade::Wrong { expected: "git status".to_string() });
+    }
+}
@@ -0,0 +1,78 @@
+use chrono::{DateTime, Utc};
+use colored::*;
+use comfy_table::{Cell, Color};
+use crate::api::new_table;
+
+/// The runs shown in the leaderboard.
+const LEADERBOARD_SIZE: usize = 10;
+
+/// A timed challenge, saved to compare it with the next ones.
+#[derive(Debug, Clone)]
+pub struct ChallengeRun {
+    pub id: Option<i64>,
+    /// Empty when the challenge had every command
+    pub tag: String,
+    pub started: DateTime<Utc>,
+    pub duration: i64,
+    pub answers: i64,
+    pub correct: i64,
+    pub score: i64,
+}
+
+/// The points of a correct answer: 10 for a command of the initial ease,
+/// more for harder commands, which have a lower ease.
+pub fn points(e_factor: f64) -> i64 {
+    (25.0 / e_factor.max(1.3)).round() as i64
+}
+
+/// The leaderboard of `tag`, or of the challenges with every command when
+/// there is no tag, which is not an overall leaderboard of every challenge.
+fn leaderboard_name(tag: Option<&str>) -> String {
+    match tag {
+        Some(tag) => format!("Leaderboard for {}", tag),
+        None => "Leaderboard for all commands".to_string(),
+    }
+}
+
+/// Shows the best `runs` of a tag, highlighting `current` if it is among them.
+pub fn show_leaderboard(tag: Option<&str>, runs: &[ChallengeRun], current: Option<&ChallengeRun>) {
+    if runs.is_empty() {
+        println!("RECLI: No challenges yet. {} is empty.", leaderboard_name(tag));
+        return;
+    }
+
+    let mut table = new_table(vec!["#", "Date", "Score", "Correct", "Duration"]);
+    for (rank, run) in runs.iter().take(LEADERBOARD_SIZE).enumerate() {
+        let is_current = current.is_some_and(|current| current.id.is_some() && current.id == run.id);
+        let color = if is_current { Color::Green } else { Color::Reset };
+
+        table.add_row(vec![
+            Cell::new(rank + 1).fg(color),
+            Cell::new(run.started.format("%Y-%m-%d %H:%M")).fg(color),
+            Cell::new(run.score).fg(color),
+            Cell::new(format!("{}/{}", run.correct, run.answers)).fg(color),
+            Cell::new(format!("{}s", run.duration)).fg(color),
+        ]);
+    }
+
+    println!("{}", leaderboard_name(tag).bold());
+    println!("{table}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn harder_commands_give_more_points() {
+        assert_eq!(points(2.5), 10);
+        assert_eq!(points(1.3), 19);
+        assert!(points(3.0) < points(2.5));
+    }
+
+    #[test]
+    fn untagged_challenges_have_a_leaderboard_of_their_own() {
+        assert_eq!(leaderboard_name(Some("git")), "Leaderboard for git");
+        assert_eq!(leaderboard_name(None), "Leaderboard for all commands");
+    }
+}
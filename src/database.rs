@@ -1,57 +1,144 @@
-use super::{Command, SRData, Mode};
-use sqlx::SqlitePool;
+use super::{CardKind, Command, SRData, Mode};
+use super::challenge::ChallengeRun;
+use super::session::{ReviewResult, ReviewSession};
+use super::tags::{is_within, renamed};
+use super::spaced_repetition::Evaluation;
+use super::cards::{bury_siblings, cloze_siblings};
+use std::time::Duration;
+use sqlx::{Row, SqlitePool};
 use sqlx::{migrate::MigrateDatabase, Sqlite};
 use chrono::{Utc, TimeZone, DateTime};
-use std::collections::VecDeque;
-use std::vec;
+use std::collections::{HashMap, HashSet, VecDeque};
 use super::utilities::get_current_date;
-use std::path::PathBuf;
+use super::profiles;
 
-fn get_database_path() -> String {
-    let mut path = match std::env::var_os("HOME") {
-        Some(home) => {
-            let mut buf = PathBuf::new();
-            buf.push(home);
-            buf
-        }
-        None => {
-            // On Windows, use the user's profile directory
-            match std::env::var_os("USERPROFILE") {
-                Some(profile) => {
-                    let mut buf = PathBuf::new();
-                    buf.push(profile);
-                    buf
-                }
-                None => {
-                    // Fallback to a default path if neither HOME nor USERPROFILE is set
-                    let mut buf = PathBuf::new();
-                    buf.push("."); // Use the current directory as a fallback
-                    buf
-                }
-            }
-        }
-    };
+/// The number of reviews in a day, counting the ones already made.
+const MAX_PER_DAY: usize = 100;
 
-    // Append a subdirectory for your application
-    path.push(".recli");
+/// The database of the profile in use, see the profiles module.
+fn get_database_path() -> String {
+    profiles::database_path().to_string_lossy().to_string()
+}
 
-    // Create the directory if it doesn't exist
-    std::fs::create_dir_all(&path).expect("Failed to create data directory");
+/// Columns added to existing tables after they were first released, as
+/// (table, column, definition).
+const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("commands", "fixtures", "TEXT"),
+    ("review_log", "hints", "INTEGER NOT NULL DEFAULT 0"),
+    ("commands", "kind", "TEXT NOT NULL DEFAULT 'Recall'"),
+    ("commands", "flag", "TEXT"),
+    ("commands", "cloze", "TEXT"),
+    ("commands", "steps", "TEXT"),
+    ("review_log", "failed_steps", "TEXT"),
+    ("commands", "note_group", "TEXT"),
+];
 
-    // Append the database file name
-    path.push("recli.db");
+/// Tables added after the first release, created on every start so that
+/// existing databases get them too.
+const TABLES: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS 'suspended_commands' (
+        'command_id'	INTEGER,
+        PRIMARY KEY('command_id'),
+        FOREIGN KEY ('command_id') REFERENCES commands('id')
+    )",
+    "CREATE TABLE IF NOT EXISTS 'rating_snapshots' (
+        'id'	INTEGER,
+        'command_id'	INTEGER NOT NULL,
+        'rated'	TEXT NOT NULL,
+        'last_review'	TEXT,
+        'mode'	TEXT NOT NULL,
+        'review_count'	INTEGER NOT NULL,
+        'n'	INTEGER NOT NULL,
+        'e_factor'	REAL NOT NULL,
+        'interval'	INTEGER NOT NULL,
+        PRIMARY KEY('id' AUTOINCREMENT),
+        FOREIGN KEY ('command_id') REFERENCES commands('id')
+    )",
+    "CREATE TABLE IF NOT EXISTS 'review_log' (
+        'id'	INTEGER,
+        'command_id'	INTEGER NOT NULL,
+        'reviewed'	TEXT NOT NULL,
+        'evaluation'	INTEGER NOT NULL,
+        'response_time'	INTEGER,
+        'mode_before'	TEXT NOT NULL,
+        'e_factor_before'	REAL NOT NULL,
+        'mode_after'	TEXT NOT NULL,
+        'e_factor_after'	REAL NOT NULL,
+        'interval_after'	INTEGER NOT NULL,
+        PRIMARY KEY('id' AUTOINCREMENT),
+        FOREIGN KEY ('command_id') REFERENCES commands('id')
+    )",
+    "CREATE TABLE IF NOT EXISTS 'response_times' (
+        'id'	INTEGER,
+        'command_id'	INTEGER NOT NULL,
+        'answered'	TEXT NOT NULL,
+        'characters'	INTEGER NOT NULL,
+        'milliseconds'	INTEGER NOT NULL,
+        PRIMARY KEY('id' AUTOINCREMENT),
+        FOREIGN KEY ('command_id') REFERENCES commands('id')
+    )",
+    "CREATE TABLE IF NOT EXISTS 'reverse_progress' (
+        'command_id'	INTEGER,
+        'created'	TEXT NOT NULL,
+        'last_review'	TEXT,
+        'mode'	TEXT NOT NULL,
+        'review_count'	INTEGER NOT NULL,
+        'n'	INTEGER NOT NULL,
+        'e_factor'	REAL NOT NULL,
+        'interval'	INTEGER NOT NULL,
+        PRIMARY KEY('command_id'),
+        FOREIGN KEY ('command_id') REFERENCES commands('id')
+    )",
+    "CREATE TABLE IF NOT EXISTS 'sibling_rules' (
+        'tag'	TEXT,
+        'tokens'	INTEGER NOT NULL,
+        PRIMARY KEY('tag')
+    )",
+    "CREATE TABLE IF NOT EXISTS 'cram_log' (
+        'id'	INTEGER,
+        'command_id'	INTEGER NOT NULL,
+        'answered'	TEXT NOT NULL,
+        'correct'	INTEGER NOT NULL,
+        'response_time'	INTEGER NOT NULL,
+        PRIMARY KEY('id' AUTOINCREMENT),
+        FOREIGN KEY ('command_id') REFERENCES commands('id')
+    )",
+    "CREATE TABLE IF NOT EXISTS 'challenge_runs' (
+        'id'	INTEGER,
+        'tag'	TEXT NOT NULL,
+        'started'	TEXT NOT NULL,
+        'duration'	INTEGER NOT NULL,
+        'answers'	INTEGER NOT NULL,
+        'correct'	INTEGER NOT NULL,
+        'score'	INTEGER NOT NULL,
+        PRIMARY KEY('id' AUTOINCREMENT)
+    )",
+    "CREATE VIRTUAL TABLE IF NOT EXISTS 'commands_fts' USING fts5(task, clues, context, commands, response, extra)",
+    "CREATE TABLE IF NOT EXISTS 'review_session' (
+        'started'	TEXT NOT NULL,
+        'original_size'	INTEGER NOT NULL,
+        'reviewed'	INTEGER NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS 'review_session_commands' (
+        'position'	INTEGER,
+        'command_id'	INTEGER NOT NULL,
+        'requeued'	INTEGER NOT NULL,
+        PRIMARY KEY('position'),
+        FOREIGN KEY ('command_id') REFERENCES commands('id')
+    )",
+];
 
-    // Convert the PathBuf to a string
-    path.to_string_lossy().to_string()
+pub async fn create_if_not_exists() -> anyhow::Result<()> {
+    create_database(&get_database_path()).await
 }
 
-pub async fn create_if_not_exists() -> anyhow::Result<()> {
-    let db_path : String = get_database_path();
+/// Creates the database at `db_path`, or adds what is missing to it.
+pub async fn create_database(db_path: &str) -> anyhow::Result<()> {
 
-    if !Sqlite::database_exists(&db_path).await.unwrap_or(false) {
-        match Sqlite::create_database(&db_path).await {
+    if !Sqlite::database_exists(db_path).await.unwrap_or(false) {
+        match Sqlite::create_database(db_path).await {
             Ok(_) => {
-                let db: sqlx::Pool<Sqlite> = SqlitePool::connect(&db_path).await.unwrap();
+                let db: sqlx::Pool<Sqlite> = SqlitePool::connect(db_path).await.unwrap();
 
                 sqlx::query!("
                     CREATE TABLE IF NOT EXISTS 'commands' (
@@ -85,9 +172,84 @@ pub async fn create_if_not_exists() -> anyhow::Result<()> {
         }
     }
 
+    let pool = SqlitePool::connect(db_path).await?;
+    for table in TABLES {
+        sqlx::query(table).execute(&pool).await?;
+    }
+
+    for (table, name, definition) in ADDED_COLUMNS {
+        let columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info($1)")
+            .bind(table)
+            .fetch_all(&pool)
+            .await?;
+
+        if !columns.iter().any(|column| column == name) {
+            sqlx::query(&format!("ALTER TABLE '{}' ADD COLUMN '{}' {}", table, name, definition))
+                .execute(&pool).await?;
+        }
+    }
+
+    // Indexes the commands saved before the search index existed
+    sqlx::query(&format!("
+        INSERT INTO 'commands_fts' (rowid, task, clues, context, commands, response, extra)
+        SELECT id, task, clues, context, replace(commands, '{}', char(10)), response, extra FROM commands
+        WHERE id NOT IN (SELECT rowid FROM commands_fts)
+    ", COMMANDS_SEPARATOR))
+        .execute(&pool).await?;
+
+    Ok(())
+}
+
+/// Separates the commands of a command in the commands table.
+const COMMANDS_SEPARATOR: &str = "<<<>>>";
+
+/// Adds `command`, saved with `id`, to the search index, replacing what was
+/// indexed for it before.
+async fn index_command(pool: &SqlitePool, id: i64, command: &Command) -> anyhow::Result<()> {
+    sqlx::query("DELETE FROM 'commands_fts' WHERE rowid = $1")
+        .bind(id)
+        .execute(pool).await?;
+
+    sqlx::query("
+        INSERT INTO 'commands_fts' (rowid, task, clues, context, commands, response, extra)
+        VALUES ($1, $2, $3, $4, $5, $6, $7);
+    ")
+        .bind(id)
+        .bind(&command.task)
+        .bind(&command.clues)
+        .bind(&command.context)
+        .bind(command.commands.join("\n"))
+        .bind(&command.response)
+        .bind(&command.extra)
+        .execute(pool).await?;
+
     Ok(())
 }
 
+async fn find_tags(pool: &SqlitePool, command_id: i64) -> anyhow::Result<Vec<String>> {
+    let tags: Vec<String> = sqlx::query_scalar("SELECT tag FROM command_tags WHERE command_id = ?")
+        .bind(command_id)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(tags)
+}
+
+/// The tags of every command, read in one query for the functions that
+/// return many commands.
+async fn find_tags_by_command(pool: &SqlitePool) -> anyhow::Result<HashMap<i64, Vec<String>>> {
+    let rows: Vec<(i64, String)> = sqlx::query_as("SELECT command_id, tag FROM command_tags")
+        .fetch_all(pool)
+        .await?;
+
+    let mut tags: HashMap<i64, Vec<String>> = HashMap::new();
+    for (command_id, tag) in rows {
+        tags.entry(command_id).or_default().push(tag);
+    }
+
+    Ok(tags)
+}
+
 fn text_to_datetime(input: &str) -> DateTime<Utc> {
     let parsed_datetime = DateTime::parse_from_rfc3339(input)
         .expect("Failed to parse datetime");
@@ -97,80 +259,135 @@ fn text_to_datetime(input: &str) -> DateTime<Utc> {
     utc_datetime
 }
 
+/// Builds a `Command` with `tags` from a row of the commands table. This is
+/// a macro because every `sqlx::query!` returns its own record type.
+macro_rules! command_from_row {
+    ($result:expr, $tags:expr) => {
+        Command { 
+            id: Some($result.id), 
+            task: $result.task, 
+            clues: $result.clues,
+            context: $result.context, 
+            prompt: $result.prompt, 
+            commands: $result.commands.as_str().split(COMMANDS_SEPARATOR).filter(|c| !c.is_empty()).map(|c| c.to_string()).collect(),
+            response: $result.response, 
+            extra: $result.extra,
+            tags: Some($tags),
+            fixtures: $result.fixtures,
+            kind: text_to_kind(&$result.kind),
+            flag: $result.flag,
+            cloze: $result.cloze,
+            group: $result.note_group,
+            steps: match $result.steps {
+                Some(steps) => serde_json::from_str(&steps)?,
+                None => vec![],
+            },
+            sr_data: SRData {
+                created: text_to_datetime(&$result.created),
+                last_review: $result.last_review.map(|review| text_to_datetime(&review)),
+                mode: text_to_mode(&$result.mode),
+                review_count: $result.review_count,
+                n: $result.n,
+                e_factor: $result.e_factor,
+                interval: $result.interval,
+            },
+        }
+    };
+}
+
+fn text_to_mode(input: &str) -> Mode {
+    match input {
+        "Learning" => Mode::Learning,
+        "Failed" => Mode::Failed,
+        _ => Mode::New
+    }
+}
+
+fn text_to_kind(input: &str) -> CardKind {
+    match input {
+        "MultipleChoice" => CardKind::MultipleChoice,
+        "Reverse" => CardKind::Reverse,
+        "Explain" => CardKind::Explain,
+        "Cloze" => CardKind::Cloze,
+        "Scenario" => CardKind::Scenario,
+        _ => CardKind::Recall
+    }
+}
+
 pub async fn find_today_commands() -> anyhow::Result<VecDeque<Command>> {
-    let mut max_per_day = 100;
     let db_path = get_database_path();
-    let pool = SqlitePool::connect(&&db_path).await?;
-
-    let mut commands:VecDeque<Command> = VecDeque::new();
+    let pool = SqlitePool::connect(&db_path).await?;
 
     let results = sqlx::query!("SELECT * FROM commands")
         .fetch_all(&pool)
         .await?;
 
-    for result in &results {
-        if result.mode == "New" { continue }
-        let last_review = text_to_datetime(&result.last_review.clone().unwrap());
+    let suspended: Vec<i64> = sqlx::query_scalar("SELECT command_id FROM suspended_commands")
+        .fetch_all(&pool)
+        .await?;
+    let mut tags = find_tags_by_command(&pool).await?;
 
-        if last_review == get_current_date() {
-            max_per_day -= 1;
-        }
+    let mut all = vec![];
+    for result in results {
+        let command_tags = tags.remove(&result.id).unwrap_or_default();
+        all.push(command_from_row!(result, command_tags));
     }
 
-    for result in results {
-        if commands.len() >= max_per_day { break; }
+    // Buried siblings don't take up the daily limit
+    let max_per_day = reviews_left_today(&all);
+    let rules = find_sibling_rules(&pool).await?;
+    let mut commands = bury_siblings(pending_commands(all, &suspended), &rules);
+    commands.truncate(max_per_day);
 
-        /*
-        let tags= sqlx::query!("
-            SELECT tag FROM command_tags WHERE command_id = ?", 
-            result.id
-        )
-            .fetch_all(&pool)
-            .await?;
+    Ok(commands)
+}
 
-        let tags:Vec<String> = tags.iter().map(|t| {
-            t.tag.clone().unwrap()
-        }).collect();
-        */
-        let command = Command { 
-            id: Some(result.id), 
-            task: result.task, 
-            clues: result.clues,
-            context: result.context, 
-            prompt: result.prompt, 
-            commands: result.commands.as_str().split("<<<>>>").map(|c| c.to_string()).collect(),
-            response: result.response, 
-            extra: result.extra,
-            tags: None,
-            sr_data: SRData {
-                created: text_to_datetime(&result.created),
-                last_review: result.last_review.map(|review| text_to_datetime(&review)),
-                mode: match result.mode.as_str() {
-                    "Learning" => Mode::Learning,
-                    "Failed" => Mode::Failed,
-                    _ => Mode::New
-                },
-                review_count: result.review_count,
-                n: result.n,
-                e_factor: result.e_factor,
-                interval: result.interval,
-            },
-        };
+/// How many of the `MAX_PER_DAY` reviews are left after the ones of
+/// `commands` already made today.
+fn reviews_left_today(commands: &[Command]) -> usize {
+    let today = get_current_date();
+    let reviewed = commands.iter()
+        .filter(|command| command.sr_data.mode != Mode::New && command.sr_data.last_review == Some(today))
+        .count();
 
-        match &command.sr_data.mode {
-            Mode::New | Mode::Failed => {
-                commands.push_back(command);
-            },
-            Mode::Learning => {
-                if command.is_pending() {
-                    commands.push_back(command);
-                }
-            }
-        }
+    MAX_PER_DAY.saturating_sub(reviewed)
+}
 
-    }
-    
-    Ok(commands)
+/// The commands due today that are not suspended.
+fn pending_commands(commands: Vec<Command>, suspended: &[i64]) -> VecDeque<Command> {
+    commands.into_iter()
+        .filter(|command| !command.id.is_some_and(|id| suspended.contains(&id)))
+        .filter(|command| command.is_pending())
+        .collect()
+}
+
+/// Returns how many first tokens are compared to find siblings, for each
+/// tag that buries them.
+async fn find_sibling_rules(pool: &SqlitePool) -> anyhow::Result<HashMap<String, usize>> {
+    let rows = sqlx::query("SELECT tag, tokens FROM sibling_rules")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows.iter().map(|row| (row.get("tag"), row.get::<i64, _>("tokens") as usize)).collect())
+}
+
+/// Makes the commands with `tag` bury their siblings, comparing `tokens`
+/// first tokens of their answers, or stops it if `tokens` is `None`.
+pub async fn save_sibling_rule(tag: &str, tokens: Option<usize>) -> anyhow::Result<()> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    match tokens {
+        Some(tokens) => sqlx::query("INSERT OR REPLACE INTO 'sibling_rules' (tag, tokens) VALUES ($1, $2)")
+            .bind(tag)
+            .bind(tokens as i64)
+            .execute(&pool).await?,
+        None => sqlx::query("DELETE FROM 'sibling_rules' WHERE tag = $1")
+            .bind(tag)
+            .execute(&pool).await?,
+    };
+
+    Ok(())
 }
 
 pub async fn find_commands() -> anyhow::Result<VecDeque<Command>> {
@@ -186,38 +403,37 @@ pub async fn find_commands() -> anyhow::Result<VecDeque<Command>> {
     ",)
         .fetch_all(&pool)
         .await?;
+    let tags = find_tags_by_command(&pool).await?;
 
     for result in results {
-        let command = Command { 
-            id: Some(result.id), 
-            task: result.task, 
-            clues: result.clues,
-            context: result.context, 
-            prompt: result.prompt, 
-            commands: result.commands.as_str().split("<<<>>>").map(|c| c.to_string()).collect(),
-            response: result.response, 
-            extra: result.extra,
-            tags: None,
-            sr_data: SRData {
-                created: text_to_datetime(&result.created),
-                last_review: result.last_review.map(|review| text_to_datetime(&review)),
-                mode: match result.mode.as_str() {
-                    "Learning" => Mode::Learning,
-                    "Failed" => Mode::Failed,
-                    _ => Mode::New
-                },
-                review_count: result.review_count,
-                n: result.n,
-                e_factor: result.e_factor,
-                interval: result.interval,
-            },
-        };
-        commands.push_back(command);
+        let command_tags = tags.get(&result.id).cloned().unwrap_or_default();
+        commands.push_back(command_from_row!(result, command_tags));
     }
 
     Ok(commands)
 }
 
+/// Returns every command, tagged or not.
+pub async fn find_all_commands() -> anyhow::Result<VecDeque<Command>> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    let mut commands:VecDeque<Command> = VecDeque::new();
+
+    let results = sqlx::query!("SELECT * FROM commands")
+        .fetch_all(&pool)
+        .await?;
+    let mut tags = find_tags_by_command(&pool).await?;
+
+    for result in results {
+        let command_tags = tags.remove(&result.id).unwrap_or_default();
+        commands.push_back(command_from_row!(result, command_tags));
+    }
+
+    Ok(commands)
+}
+
+/// Returns the commands with `tag` or one of its children.
 pub async fn find_commands_with_tag(tag:&str) -> anyhow::Result<VecDeque<Command>> {
     let db_path = get_database_path();
     let pool = SqlitePool::connect(&db_path).await?;
@@ -228,54 +444,129 @@ pub async fn find_commands_with_tag(tag:&str) -> anyhow::Result<VecDeque<Command
         SELECT c.*
         FROM commands AS c
         INNER JOIN command_tags AS ct ON c.id = ct.command_id
-        WHERE ct.tag = ?
+        WHERE ct.tag = ?1 OR substr(ct.tag, 1, length(?1) + 2) = ?1 || '::'
+        GROUP BY c.id
     ", tag)
         .fetch_all(&pool)
         .await?;
+    let mut tags = find_tags_by_command(&pool).await?;
 
     for result in results {
-        let command = Command { 
-            id: Some(result.id), 
-            task: result.task, 
-            clues: result.clues,
-            context: result.context, 
-            prompt: result.prompt, 
-            commands: result.commands.as_str().split("<<<>>>").map(|c| c.to_string()).collect(),
-            response: result.response, 
-            extra: result.extra,
-            tags: None,
-            sr_data: SRData {
-                created: text_to_datetime(&result.created),
-                last_review: result.last_review.map(|review| text_to_datetime(&review)),
-                mode: match result.mode.as_str() {
-                    "Learning" => Mode::Learning,
-                    "Failed" => Mode::Failed,
-                    _ => Mode::New
-                },
-                review_count: result.review_count,
-                n: result.n,
-                e_factor: result.e_factor,
-                interval: result.interval,
-            },
-        };
-        commands.push_back(command);
+        let command_tags = tags.remove(&result.id).unwrap_or_default();
+        commands.push_back(command_from_row!(result, command_tags));
     }
 
     Ok(commands)
 }
 
-pub async fn save_commands(commands: &Vec<Command>) -> anyhow::Result<()> {
+pub async fn find_command(id: i64) -> anyhow::Result<Option<Command>> {
     let db_path = get_database_path();
     let pool = SqlitePool::connect(&db_path).await?;
 
-    let sr_data = SRData::default();
+    let result = sqlx::query!("SELECT * FROM commands WHERE id = ?", id)
+        .fetch_optional(&pool)
+        .await?;
+
+    match result {
+        Some(result) => {
+            let tags = find_tags(&pool, result.id).await?;
+            Ok(Some(command_from_row!(result, tags)))
+        },
+        None => Ok(None),
+    }
+}
+
+/// Returns the recall commands due for a reverse review today, with the
+/// schedule of the reverse direction as their `sr_data`. Commands never
+/// reviewed in reverse are new.
+pub async fn find_reverse_commands() -> anyhow::Result<VecDeque<Command>> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    let results = sqlx::query!("SELECT * FROM commands WHERE kind = 'Recall'")
+        .fetch_all(&pool)
+        .await?;
+
+    let suspended: Vec<i64> = sqlx::query_scalar("SELECT command_id FROM suspended_commands")
+        .fetch_all(&pool)
+        .await?;
+    let mut tags = find_tags_by_command(&pool).await?;
+    let mut progress = find_reverse_progress_by_command(&pool).await?;
+
+    let mut all = vec![];
+    for result in results {
+        let command_tags = tags.remove(&result.id).unwrap_or_default();
+        let mut command = command_from_row!(result, command_tags);
+        command.sr_data = progress.remove(&result.id).unwrap_or_default();
+        all.push(command);
+    }
+
+    // The reverse reviews have a daily limit of their own
+    let max_per_day = reviews_left_today(&all);
+    let mut commands = pending_commands(all, &suspended);
+    commands.truncate(max_per_day);
+
+    Ok(commands)
+}
+
+async fn find_reverse_progress_by_command(pool: &SqlitePool) -> anyhow::Result<HashMap<i64, SRData>> {
+    let rows = sqlx::query("SELECT * FROM reverse_progress")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows.iter().map(|row| (row.get("command_id"), SRData {
+        created: text_to_datetime(row.get("created")),
+        last_review: row.get::<Option<&str>, _>("last_review").map(text_to_datetime),
+        mode: text_to_mode(row.get("mode")),
+        review_count: row.get("review_count"),
+        n: row.get("n"),
+        e_factor: row.get("e_factor"),
+        interval: row.get("interval"),
+    })).collect())
+}
 
-    for command in commands {
-        let commands: String = command.commands.join("<<<>>>");
-        let id = sqlx::query!(
+/// Saves `command.sr_data` as the schedule of the reverse direction.
+pub async fn update_reverse_progress(command: &Command) -> anyhow::Result<()> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+    let sr_data = &command.sr_data;
+
+    sqlx::query("
+        INSERT OR REPLACE INTO 'reverse_progress' (command_id, created, last_review, mode, review_count, n, e_factor, interval)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8);
+    ")
+        .bind(command.id.expect("The id is expected in order to update."))
+        .bind(sr_data.created)
+        .bind(sr_data.last_review)
+        .bind(sr_data.mode.to_string())
+        .bind(sr_data.review_count)
+        .bind(sr_data.n)
+        .bind(sr_data.e_factor)
+        .bind(sr_data.interval)
+        .execute(&pool).await?;
+
+    Ok(())
+}
+
+/// Saves the commands that are not already stored and returns how many were saved.
+pub async fn save_commands(commands: &[Command]) -> anyhow::Result<usize> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+    let mut saved = 0;
+
+    // Each gap of a cloze card is saved as a sibling card
+    for command in commands.iter().flat_map(cloze_siblings) {
+        let sr_data = &command.sr_data;
+        let commands: String = command.commands.join(COMMANDS_SEPARATOR);
+        // Steps are saved as JSON, they are only read with the card
+        let steps = match command.steps.is_empty() {
+            true => None,
+            false => Some(serde_json::to_string(&command.steps)?),
+        };
+        let result = sqlx::query!(
             r#"
-                INSERT OR IGNORE INTO 'commands' (task, clues, context, prompt, commands, response, extra, created, last_review, mode, review_count, n, e_factor, interval) VALUES 
-                (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14);
+                INSERT OR IGNORE INTO 'commands' (task, clues, context, prompt, commands, response, extra, created, last_review, mode, review_count, n, e_factor, interval, fixtures, kind, flag, cloze, steps, note_group) VALUES 
+                (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20);
             "#,
             command.task,
             command.clues,
@@ -290,11 +581,20 @@ pub async fn save_commands(commands: &Vec<Command>) -> anyhow::Result<()> {
             sr_data.review_count,
             sr_data.n,
             sr_data.e_factor,
-            sr_data.interval
+            sr_data.interval,
+            command.fixtures,
+            command.kind,
+            command.flag,
+            command.cloze,
+            steps,
+            command.group
         )
-        .execute(&pool).await?.last_insert_rowid();
+        .execute(&pool).await?;
 
-        if id == 0 { continue; }
+        if result.rows_affected() == 0 { continue; }
+        let id = result.last_insert_rowid();
+        saved += 1;
+        index_command(&pool, id, &command).await?;
 
         if let Some(tags) = &command.tags {
             for tag in tags {
@@ -311,9 +611,7 @@ pub async fn save_commands(commands: &Vec<Command>) -> anyhow::Result<()> {
         }
     }
 
-    //println!("{} commands saved.", commands.len());
-
-    Ok(())
+    Ok(saved)
 }
 
 pub async fn update_command(command: &Command) -> anyhow::Result<()> {
@@ -340,8 +638,713 @@ pub async fn update_command(command: &Command) -> anyhow::Result<()> {
         .bind(sr_data.n)
         .bind(sr_data.e_factor)
         .bind(sr_data.interval)
-        .bind(&command.id.expect("The id is expected in order to update."))
+        .bind(command.id.expect("The id is expected in order to update."))
+        .execute(&pool).await?;
+
+    Ok(())
+}
+
+pub async fn suspend_command(command: &Command) -> anyhow::Result<()> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    sqlx::query("INSERT OR IGNORE INTO 'suspended_commands' (command_id) VALUES ($1)")
+        .bind(command.id.expect("The id is expected in order to suspend."))
+        .execute(&pool).await?;
+
+    Ok(())
+}
+
+/// Reviews the commands with `ids` again and returns how many were suspended.
+pub async fn unsuspend_commands(ids: &[i64]) -> anyhow::Result<u64> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+    let mut unsuspended = 0;
+
+    for id in ids {
+        unsuspended += sqlx::query("DELETE FROM 'suspended_commands' WHERE command_id = $1")
+            .bind(id)
+            .execute(&pool).await?
+            .rows_affected();
+    }
+
+    Ok(unsuspended)
+}
+
+/// Stores the schedule a command has before being rated so that the rating
+/// can be undone later in the day. Snapshots from previous days are dropped.
+pub async fn save_rating_snapshot(command: &Command) -> anyhow::Result<()> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+    let today = get_current_date();
+
+    sqlx::query("DELETE FROM 'rating_snapshots' WHERE rated <> $1")
+        .bind(today)
+        .execute(&pool).await?;
+
+    let sr_data = &command.sr_data;
+
+    sqlx::query("
+        INSERT INTO 'rating_snapshots' (command_id, rated, last_review, mode, review_count, n, e_factor, interval)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8);
+    ")
+        .bind(command.id.expect("The id is expected in order to save a snapshot."))
+        .bind(today)
+        .bind(sr_data.last_review)
+        .bind(sr_data.mode.to_string())
+        .bind(sr_data.review_count)
+        .bind(sr_data.n)
+        .bind(sr_data.e_factor)
+        .bind(sr_data.interval)
+        .execute(&pool).await?;
+
+    Ok(())
+}
+
+/// Restores the last command rated today to the schedule it had before the
+/// rating, and returns it.
+pub async fn undo_last_rating() -> anyhow::Result<Option<Command>> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    let snapshot = sqlx::query("SELECT * FROM 'rating_snapshots' WHERE rated = $1 ORDER BY id DESC LIMIT 1")
+        .bind(get_current_date())
+        .fetch_optional(&pool)
+        .await?;
+
+    let Some(snapshot) = snapshot else { return Ok(None) };
+
+    sqlx::query("DELETE FROM 'rating_snapshots' WHERE id = $1")
+        .bind(snapshot.get::<i64, _>("id"))
+        .execute(&pool).await?;
+
+    sqlx::query("DELETE FROM 'review_log' WHERE id = (SELECT MAX(id) FROM 'review_log' WHERE command_id = $1)")
+        .bind(snapshot.get::<i64, _>("command_id"))
+        .execute(&pool).await?;
+
+    let Some(mut command) = find_command(snapshot.get("command_id")).await? else { return Ok(None) };
+
+    command.sr_data = SRData {
+        created: command.sr_data.created,
+        last_review: snapshot.get("last_review"),
+        mode: text_to_mode(snapshot.get("mode")),
+        review_count: snapshot.get("review_count"),
+        n: snapshot.get("n"),
+        e_factor: snapshot.get("e_factor"),
+        interval: snapshot.get("interval"),
+    };
+
+    update_command(&command).await?;
+
+    Ok(Some(command))
+}
+
+/// Returns the saved review session and the ids of its queued commands, in order.
+pub async fn find_review_session() -> anyhow::Result<Option<(ReviewSession, Vec<i64>)>> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    let Some(result) = sqlx::query("SELECT * FROM 'review_session'")
+        .fetch_optional(&pool)
+        .await? else { return Ok(None) };
+
+    let queue = sqlx::query("SELECT command_id, requeued FROM 'review_session_commands' ORDER BY position")
+        .fetch_all(&pool)
+        .await?;
+
+    let session = ReviewSession {
+        started: result.get("started"),
+        original_size: result.get::<i64, _>("original_size") as usize,
+        reviewed: result.get("reviewed"),
+        requeued: queue.iter()
+            .filter(|row| row.get::<bool, _>("requeued"))
+            .map(|row| row.get("command_id"))
+            .collect(),
+    };
+
+    Ok(Some((session, queue.iter().map(|row| row.get("command_id")).collect())))
+}
+
+pub async fn save_review_session(session: &ReviewSession, commands: &VecDeque<Command>) -> anyhow::Result<()> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+    let mut transaction = pool.begin().await?;
+
+    sqlx::query("DELETE FROM 'review_session'").execute(&mut *transaction).await?;
+    sqlx::query("DELETE FROM 'review_session_commands'").execute(&mut *transaction).await?;
+
+    sqlx::query("INSERT INTO 'review_session' (started, original_size, reviewed) VALUES ($1, $2, $3)")
+        .bind(session.started)
+        .bind(session.original_size as i64)
+        .bind(session.reviewed)
+        .execute(&mut *transaction).await?;
+
+    for (position, command) in commands.iter().enumerate() {
+        let Some(id) = command.id else { continue };
+
+        sqlx::query("INSERT INTO 'review_session_commands' (position, command_id, requeued) VALUES ($1, $2, $3)")
+            .bind(position as i64)
+            .bind(id)
+            .bind(session.requeued.contains(&id))
+            .execute(&mut *transaction).await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+pub async fn delete_review_session() -> anyhow::Result<()> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    sqlx::query("DELETE FROM 'review_session'").execute(&pool).await?;
+    sqlx::query("DELETE FROM 'review_session_commands'").execute(&pool).await?;
+
+    Ok(())
+}
+
+/// Records a rating in the review history. `before` is the schedule the
+/// command had before being rated. `failed_steps` are the tasks of the
+/// steps of a scenario card answered wrong.
+pub async fn save_review_log(command: &Command, before: &SRData, evaluation: &Evaluation, response_time: Duration, hints: usize, failed_steps: &[String]) -> anyhow::Result<()> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    sqlx::query("
+        INSERT INTO 'review_log' (command_id, reviewed, evaluation, response_time, mode_before, e_factor_before, mode_after, e_factor_after, interval_after, hints, failed_steps)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11);
+    ")
+        .bind(command.id.expect("The id is expected in order to log a review."))
+        .bind(Utc::now())
+        .bind(evaluation.get_num())
+        .bind(response_time.as_millis() as i64)
+        .bind(before.mode.to_string())
+        .bind(before.e_factor)
+        .bind(command.sr_data.mode.to_string())
+        .bind(command.sr_data.e_factor)
+        .bind(command.sr_data.interval)
+        .bind(hints as i64)
+        .bind(Some(failed_steps.join("\n")).filter(|steps| !steps.is_empty()))
+        .execute(&pool).await?;
+
+    Ok(())
+}
+
+/// Returns the reviews made since `since`, oldest first.
+pub async fn find_review_log(since: DateTime<Utc>) -> anyhow::Result<Vec<ReviewResult>> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    let tags = find_tags_by_command(&pool).await?;
+    let rows = sqlx::query("
+        SELECT l.*, c.task
+        FROM review_log AS l
+        INNER JOIN commands AS c ON c.id = l.command_id
+        WHERE l.reviewed >= $1
+        ORDER BY l.id
+    ")
+        .bind(since)
+        .fetch_all(&pool)
+        .await?;
+
+    let mut results = vec![];
+    for row in rows {
+        let command_id: i64 = row.get("command_id");
+        results.push(ReviewResult {
+            command_id,
+            reviewed: row.get("reviewed"),
+            mode_before: text_to_mode(row.get("mode_before")),
+            task: row.get("task"),
+            tags: tags.get(&command_id).cloned().unwrap_or_default(),
+            is_correct: row.get::<i64, _>("evaluation") >= 3,
+            response_time: row.get::<Option<i64>, _>("response_time").map(|ms| Duration::from_millis(ms as u64)),
+            hints: row.get::<i64, _>("hints") as usize,
+            e_factor_before: Some(row.get("e_factor_before")),
+            e_factor_after: Some(row.get("e_factor_after")),
+            lapsed: row.get::<&str, _>("mode_before") == "Learning" && row.get::<&str, _>("mode_after") == "Failed",
+            failed_steps: row.get::<Option<&str>, _>("failed_steps")
+                .map(|steps| steps.lines().map(|step| step.to_string()).collect())
+                .unwrap_or_default(),
+        });
+    }
+
+    Ok(results)
+}
+
+/// Records an answer given while cramming, kept apart from the review log.
+pub async fn save_cram_answer(command: &Command, is_correct: bool, response_time: Duration) -> anyhow::Result<()> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    sqlx::query("
+        INSERT INTO 'cram_log' (command_id, answered, correct, response_time)
+        VALUES ($1, $2, $3, $4);
+    ")
+        .bind(command.id.expect("The id is expected in order to log a cram answer."))
+        .bind(Utc::now())
+        .bind(is_correct)
+        .bind(response_time.as_millis() as i64)
+        .execute(&pool).await?;
+
+    Ok(())
+}
+
+/// Returns the answers given while cramming since `since`, oldest first.
+pub async fn find_cram_log(since: DateTime<Utc>) -> anyhow::Result<Vec<ReviewResult>> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    let tags = find_tags_by_command(&pool).await?;
+    let rows = sqlx::query("
+        SELECT l.*, c.task, c.mode
+        FROM cram_log AS l
+        INNER JOIN commands AS c ON c.id = l.command_id
+        WHERE l.answered >= $1
+        ORDER BY l.id
+    ")
+        .bind(since)
+        .fetch_all(&pool)
+        .await?;
+
+    let mut results = vec![];
+    for row in rows {
+        let command_id: i64 = row.get("command_id");
+        results.push(ReviewResult {
+            command_id,
+            reviewed: row.get("answered"),
+            mode_before: text_to_mode(row.get("mode")),
+            task: row.get("task"),
+            tags: tags.get(&command_id).cloned().unwrap_or_default(),
+            is_correct: row.get("correct"),
+            response_time: Some(Duration::from_millis(row.get::<i64, _>("response_time") as u64)),
+            hints: 0,
+            e_factor_before: None,
+            e_factor_after: None,
+            lapsed: false,
+            failed_steps: vec![],
+        });
+    }
+
+    Ok(results)
+}
+
+/// Saves a challenge and returns its id.
+pub async fn save_challenge_run(run: &ChallengeRun) -> anyhow::Result<i64> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    let result = sqlx::query("
+        INSERT INTO 'challenge_runs' (tag, started, duration, answers, correct, score)
+        VALUES ($1, $2, $3, $4, $5, $6);
+    ")
+        .bind(&run.tag)
+        .bind(run.started)
+        .bind(run.duration)
+        .bind(run.answers)
+        .bind(run.correct)
+        .bind(run.score)
+        .execute(&pool).await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+/// Returns the challenges of `tag`, or the ones with every command when
+/// `None`, the best first. The first one is the personal best.
+pub async fn find_challenge_runs(tag: Option<&str>) -> anyhow::Result<Vec<ChallengeRun>> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    let rows = sqlx::query("SELECT * FROM challenge_runs WHERE tag = $1 ORDER BY score DESC, id")
+        .bind(tag.unwrap_or_default())
+        .fetch_all(&pool)
+        .await?;
+
+    Ok(rows.iter().map(|row| ChallengeRun {
+        id: Some(row.get("id")),
+        tag: row.get("tag"),
+        started: row.get("started"),
+        duration: row.get("duration"),
+        answers: row.get("answers"),
+        correct: row.get("correct"),
+        score: row.get("score"),
+    }).collect())
+}
+
+/// Returns the commands matching every word of `query`, the best matches
+/// first, each with a snippet of its text where the matches are marked as
+/// `\u{1}match\u{2}`.
+pub async fn search_commands(query: &str) -> anyhow::Result<Vec<(Command, String)>> {
+    if query.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    let fts_query = fts_query(query);
+    let rows = sqlx::query!(r#"
+        SELECT commands.*, snippet(commands_fts, -1, char(1), char(2), '…', 12) AS "snippet!: String"
+        FROM commands_fts
+        JOIN commands ON commands.id = commands_fts.rowid
+        WHERE commands_fts MATCH $1
+        ORDER BY rank
+    "#, fts_query)
+        .fetch_all(&pool)
+        .await?;
+    let mut tags = find_tags_by_command(&pool).await?;
+
+    let mut results = vec![];
+    for row in rows {
+        let command_tags = tags.remove(&row.id).unwrap_or_default();
+        let snippet = row.snippet.clone();
+        results.push((command_from_row!(row, command_tags), snippet));
+    }
+
+    Ok(results)
+}
+
+/// Quotes the words of `query` so that symbols such as `-` or `:` are
+/// searched instead of read as FTS5 operators. The last word matches as a
+/// prefix, to find `git stash` by typing `git st`.
+fn fts_query(query: &str) -> String {
+    let words: Vec<String> = query.split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+
+    format!("{}*", words.join(" "))
+}
+
+/// The number of commands in the database at `db_path`, of any profile.
+pub async fn count_commands(db_path: &str) -> anyhow::Result<i64> {
+    let pool = SqlitePool::connect(db_path).await?;
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM commands")
+        .fetch_one(&pool)
+        .await?;
+
+    Ok(count)
+}
+
+pub async fn find_suspended() -> anyhow::Result<Vec<i64>> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    let ids: Vec<i64> = sqlx::query_scalar("SELECT command_id FROM suspended_commands")
+        .fetch_all(&pool)
+        .await?;
+
+    Ok(ids)
+}
+
+/// Tags the commands with `ids` and returns how many didn't have the tag.
+pub async fn add_tag(ids: &[i64], tag: &str) -> anyhow::Result<u64> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+    let mut transaction = pool.begin().await?;
+    let mut added = 0;
+
+    for id in ids {
+        added += sqlx::query("INSERT OR IGNORE INTO 'command_tags' (tag, command_id) VALUES ($1, $2)")
+            .bind(tag)
+            .bind(id)
+            .execute(&mut *transaction).await?
+            .rows_affected();
+    }
+    transaction.commit().await?;
+
+    Ok(added)
+}
+
+/// Removes `tag` and its children from the commands with `ids`, or from
+/// every command if `ids` is `None`. Returns how many commands had them.
+pub async fn remove_tag(ids: Option<&[i64]>, tag: &str) -> anyhow::Result<usize> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+    // Either every row is changed or none is
+    let mut transaction = pool.begin().await?;
+
+    let rows = sqlx::query("SELECT tag, command_id FROM command_tags")
+        .fetch_all(&mut *transaction)
+        .await?;
+
+    let mut commands: HashSet<i64> = HashSet::new();
+    for row in rows {
+        let (row_tag, command_id): (String, i64) = (row.get("tag"), row.get("command_id"));
+        if !is_within(&row_tag, tag) || ids.is_some_and(|ids| !ids.contains(&command_id)) {
+            continue;
+        }
+
+        sqlx::query("DELETE FROM 'command_tags' WHERE tag = $1 AND command_id = $2")
+            .bind(&row_tag)
+            .bind(command_id)
+            .execute(&mut *transaction).await?;
+        commands.insert(command_id);
+    }
+    transaction.commit().await?;
+
+    Ok(commands.len())
+}
+
+/// Renames `old` and its children to `new`, with their sibling rules,
+/// returning how many commands had them.
+pub async fn rename_tag(old: &str, new: &str) -> anyhow::Result<usize> {
+    // Renaming a tag into itself would remove it after adding it again
+    if is_within(new, old) {
+        anyhow::bail!("A tag can't be renamed to itself or one of its children");
+    }
+
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+    let mut transaction = pool.begin().await?;
+
+    let rows = sqlx::query("SELECT tag, command_id FROM command_tags")
+        .fetch_all(&mut *transaction)
+        .await?;
+
+    let mut commands: HashSet<i64> = HashSet::new();
+    for row in rows {
+        let (tag, command_id): (String, i64) = (row.get("tag"), row.get("command_id"));
+        let Some(renamed) = renamed(&tag, old, new) else { continue };
+
+        // The command may have the new tag already
+        sqlx::query("INSERT OR IGNORE INTO 'command_tags' (tag, command_id) VALUES ($1, $2)")
+            .bind(&renamed)
+            .bind(command_id)
+            .execute(&mut *transaction).await?;
+        sqlx::query("DELETE FROM 'command_tags' WHERE tag = $1 AND command_id = $2")
+            .bind(&tag)
+            .bind(command_id)
+            .execute(&mut *transaction).await?;
+        commands.insert(command_id);
+    }
+
+    let rules = sqlx::query("SELECT tag, tokens FROM sibling_rules")
+        .fetch_all(&mut *transaction)
+        .await?;
+    for row in rules {
+        let (tag, tokens): (String, i64) = (row.get("tag"), row.get("tokens"));
+        let Some(renamed) = renamed(&tag, old, new) else { continue };
+
+        sqlx::query("INSERT OR REPLACE INTO 'sibling_rules' (tag, tokens) VALUES ($1, $2)")
+            .bind(&renamed)
+            .bind(tokens)
+            .execute(&mut *transaction).await?;
+        sqlx::query("DELETE FROM 'sibling_rules' WHERE tag = $1")
+            .bind(&tag)
+            .execute(&mut *transaction).await?;
+    }
+    transaction.commit().await?;
+
+    Ok(commands.len())
+}
+
+/// Returns the ids of the commands failed in a review since `since`.
+pub async fn find_failed_since(since: DateTime<Utc>) -> anyhow::Result<HashSet<i64>> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    let ids: Vec<i64> = sqlx::query_scalar("SELECT DISTINCT command_id FROM review_log WHERE reviewed >= $1 AND evaluation < 3")
+        .bind(since)
+        .fetch_all(&pool)
+        .await?;
+
+    Ok(ids.into_iter().collect())
+}
+
+/// Saves the time the user took to type `answer`, a correct answer of `command`.
+pub async fn save_response_time(command: &Command, answer: &str, response_time: Duration) -> anyhow::Result<()> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    sqlx::query("
+        INSERT INTO 'response_times' (command_id, answered, characters, milliseconds)
+        VALUES ($1, $2, $3, $4);
+    ")
+        .bind(command.id.expect("The id is expected in order to save a response time."))
+        .bind(Utc::now())
+        .bind(answer.chars().count() as i64)
+        .bind(response_time.as_millis() as i64)
         .execute(&pool).await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Returns the latest response times of `command`, and the latest response
+/// times of all the commands with the length of their answers.
+pub async fn find_response_times(command: &Command) -> anyhow::Result<(Vec<Duration>, Vec<(Duration, usize)>)> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    let command_times = sqlx::query_scalar::<_, i64>("
+        SELECT milliseconds FROM response_times
+        WHERE command_id = $1
+        ORDER BY id DESC LIMIT 10
+    ")
+        .bind(command.id)
+        .fetch_all(&pool)
+        .await?
+        .into_iter()
+        .map(|milliseconds| Duration::from_millis(milliseconds as u64))
+        .collect();
+
+    let user_times = sqlx::query("SELECT milliseconds, characters FROM response_times ORDER BY id DESC LIMIT 50")
+        .fetch_all(&pool)
+        .await?
+        .iter()
+        .map(|row| (
+            Duration::from_millis(row.get::<i64, _>("milliseconds") as u64),
+            row.get::<i64, _>("characters") as usize,
+        ))
+        .collect();
+
+    Ok((command_times, user_times))
+}
+
+/// Counts the commands, not counting the suspended ones, that have to be reviewed on `date`.
+pub async fn count_due_on(date: DateTime<Utc>) -> anyhow::Result<usize> {
+    let db_path = get_database_path();
+    let pool = SqlitePool::connect(&db_path).await?;
+
+    let rows = sqlx::query("
+        SELECT mode, last_review, interval FROM commands
+        WHERE id NOT IN (SELECT command_id FROM suspended_commands)
+    ")
+        .fetch_all(&pool)
+        .await?;
+
+    let count = rows.iter().filter(|row| {
+        let sr_data = SRData {
+            last_review: row.get("last_review"),
+            mode: text_to_mode(row.get("mode")),
+            interval: row.get("interval"),
+            ..Default::default()
+        };
+        sr_data.is_due_on(date)
+    }).count();
+
+    Ok(count)
+}
+
+/// Creates an empty database in a temporary directory, used instead of the
+/// profile's one by the test running on this thread until it is dropped.
+#[cfg(test)]
+pub async fn create_test_database() -> tempfile::TempDir {
+    let directory = tempfile::tempdir().unwrap();
+    profiles::use_test_database(directory.path().join("recli.db"));
+    create_if_not_exists().await.unwrap();
+
+    directory
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_words_are_quoted() {
+        assert_eq!(fts_query("git st"), "\"git\" \"st\"*");
+        assert_eq!(fts_query("--amend \"x"), "\"--amend\" \"\"\"x\"*");
+    }
+
+    #[tokio::test]
+    async fn undoing_a_rating_restores_the_snapshot() {
+        let _database = create_test_database().await;
+        let command: Command = toml::from_str("task = \"List the files\"\ncommands = [\"ls\"]").unwrap();
+        save_commands(&[command]).await.unwrap();
+
+        let mut command = find_all_commands().await.unwrap().pop_front().unwrap();
+        let before = command.sr_data.clone();
+        save_rating_snapshot(&command).await.unwrap();
+        command.sr_data.mode = Mode::Learning;
+        command.sr_data.interval = 6;
+        command.sr_data.e_factor = 2.6;
+        command.sr_data.last_review = Some(get_current_date());
+        update_command(&command).await.unwrap();
+        save_review_log(&command, &before, &Evaluation::Perfect, Duration::ZERO, 0, &[]).await.unwrap();
+
+        let restored = undo_last_rating().await.unwrap().unwrap();
+        let saved = find_command(command.id.unwrap()).await.unwrap().unwrap();
+
+        for sr_data in [&restored.sr_data, &saved.sr_data] {
+            assert_eq!(sr_data.mode, before.mode);
+            assert_eq!(sr_data.interval, before.interval);
+            assert_eq!(sr_data.e_factor, before.e_factor);
+            assert_eq!(sr_data.last_review, before.last_review);
+        }
+        let pool = SqlitePool::connect(&get_database_path()).await.unwrap();
+        let logged: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM review_log").fetch_one(&pool).await.unwrap();
+        assert_eq!(logged, 0);
+        assert!(undo_last_rating().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn buried_siblings_leave_room_in_the_daily_limit() {
+        let _database = create_test_database().await;
+        let command = |answer: &str, tag: &str| -> Command {
+            toml::from_str(&format!("task = \"{}\"\ncommands = [\"{}\"]\ntags = [\"{}\"]", answer, answer, tag)).unwrap()
+        };
+        // Two siblings first, then enough other commands to fill the day
+        let mut commands = vec![command("git stash", "git"), command("git stash pop", "git")];
+        commands.extend((0..100).map(|number| command(&format!("echo {}", number), "shell")));
+        save_commands(&commands).await.unwrap();
+        save_sibling_rule("git", Some(1)).await.unwrap();
+
+        let queue = find_today_commands().await.unwrap();
+
+        assert_eq!(queue.len(), 100);
+        assert!(queue.iter().all(|command| command.task != "git stash pop"));
+    }
+
+    #[tokio::test]
+    async fn reverse_reviews_made_today_count_against_the_limit() {
+        let _database = create_test_database().await;
+        let commands: Vec<Command> = (0..MAX_PER_DAY + 1)
+            .map(|number| toml::from_str(&format!("task = \"Print {}\"\ncommands = [\"echo {}\"]", number, number)).unwrap())
+            .collect();
+        save_commands(&commands).await.unwrap();
+
+        for mut command in find_all_commands().await.unwrap().into_iter().take(2) {
+            command.sr_data.mode = Mode::Learning;
+            command.sr_data.interval = 6;
+            command.sr_data.last_review = Some(get_current_date());
+            update_reverse_progress(&command).await.unwrap();
+        }
+
+        assert_eq!(find_reverse_commands().await.unwrap().len(), MAX_PER_DAY - 2);
+        // The reviews of the other direction are counted apart
+        assert_eq!(find_today_commands().await.unwrap().len(), MAX_PER_DAY);
+    }
+
+    #[tokio::test]
+    async fn renaming_a_tag_moves_its_sibling_rules() {
+        let _database = create_test_database().await;
+        let command: Command = toml::from_str("task = \"Stash\"\ncommands = [\"git stash\"]\ntags = [\"git::stash\"]").unwrap();
+        save_commands(&[command]).await.unwrap();
+        save_sibling_rule("git", Some(2)).await.unwrap();
+
+        assert!(rename_tag("git", "git").await.is_err());
+        assert!(rename_tag("git", "git::old").await.is_err());
+        assert_eq!(rename_tag("git", "vcs").await.unwrap(), 1);
+
+        let pool = SqlitePool::connect(&get_database_path()).await.unwrap();
+        let rules = find_sibling_rules(&pool).await.unwrap();
+        assert_eq!(rules, HashMap::from([("vcs".to_string(), 2)]));
+        let command = find_all_commands().await.unwrap().pop_front().unwrap();
+        assert_eq!(command.tags, Some(vec!["vcs::stash".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn unsuspended_commands_are_reviewed_again() {
+        let _database = create_test_database().await;
+        let command: Command = toml::from_str("task = \"List the files\"\ncommands = [\"ls\"]").unwrap();
+        save_commands(&[command]).await.unwrap();
+        let command = find_all_commands().await.unwrap().pop_front().unwrap();
+
+        suspend_command(&command).await.unwrap();
+        assert!(find_today_commands().await.unwrap().is_empty());
+
+        assert_eq!(unsuspend_commands(&[command.id.unwrap(), 42]).await.unwrap(), 1);
+        assert!(find_suspended().await.unwrap().is_empty());
+        assert_eq!(find_today_commands().await.unwrap().len(), 1);
+    }
+}
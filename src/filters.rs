@@ -0,0 +1,200 @@
+use std::collections::{HashSet, VecDeque};
+use anyhow::bail;
+use chrono::Duration;
+use rand::seq::SliceRandom;
+use crate::utilities::get_current_date;
+use crate::tags::is_within;
+use super::database;
+use super::{Command, Mode};
+
+/// Which commands to practice and how, besides their tags.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct PracticeOptions {
+    /// Only commands found by this search
+    #[arg(long, value_name = "QUERY")]
+    pub search: Option<String>,
+    /// Only commands in this mode
+    #[arg(long, value_enum)]
+    pub mode: Option<Mode>,
+    /// Only commands whose ease is below this percentage, the weakest ones
+    #[arg(long, value_name = "PERCENT")]
+    pub ease_below: Option<f64>,
+    /// Only commands failed in the reviews of the last days
+    #[arg(long, value_name = "DAYS")]
+    pub failed_within: Option<i64>,
+    /// Practice a random sample of this many commands
+    #[arg(long, value_name = "COUNT")]
+    pub sample: Option<usize>,
+    /// Practice the commands in a random order
+    #[arg(long)]
+    pub shuffle: bool,
+    /// Stop the practice after this many minutes
+    #[arg(long, value_name = "MINUTES")]
+    pub time_limit: Option<u64>,
+}
+
+impl PracticeOptions {
+    /// The saved commands matching `tags`, an expression split in words, and
+    /// the filters.
+    pub async fn find_commands(&self, tags: &[String]) -> anyhow::Result<VecDeque<Command>> {
+        let tags = match tags.is_empty() {
+            true => None,
+            false => Some(TagExpr::parse(&tags.join(" "))?),
+        };
+
+        let commands = match &self.search {
+            Some(query) => database::search_commands(query).await?.into_iter().map(|(command, _)| command).collect(),
+            None => database::find_all_commands().await?,
+        };
+
+        self.apply(commands, tags.as_ref()).await
+    }
+
+    pub fn time_limit(&self) -> Option<std::time::Duration> {
+        self.time_limit.map(|minutes| std::time::Duration::from_secs(minutes * 60))
+    }
+
+    /// Keeps the commands that match `tags` and the filters, sampled and
+    /// shuffled as asked.
+    pub async fn apply(&self, commands: VecDeque<Command>, tags: Option<&TagExpr>) -> anyhow::Result<VecDeque<Command>> {
+        let failed: Option<HashSet<i64>> = match self.failed_within {
+            Some(days) => Some(database::find_failed_since(get_current_date() - Duration::days(days)).await?),
+            None => None,
+        };
+
+        let mut commands: Vec<Command> = commands.into_iter().filter(|command| {
+            tags.is_none_or(|tags| tags.matches(command.tags.as_deref().unwrap_or_default()))
+                && self.mode.as_ref().is_none_or(|mode| command.sr_data.mode == *mode)
+                && self.ease_below.is_none_or(|ease| command.sr_data.e_factor * 100.0 < ease)
+                && failed.as_ref().is_none_or(|failed| command.id.is_some_and(|id| failed.contains(&id)))
+        }).collect();
+
+        let mut rng = rand::thread_rng();
+        if let Some(sample) = self.sample {
+            commands = commands.choose_multiple(&mut rng, sample).cloned().collect();
+        }
+        if self.shuffle {
+            commands.shuffle(&mut rng);
+        }
+
+        Ok(commands.into())
+    }
+}
+
+/// A boolean expression of tags, such as `git AND NOT (basics OR config)`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TagExpr {
+    Tag(String),
+    Not(Box<TagExpr>),
+    And(Box<TagExpr>, Box<TagExpr>),
+    Or(Box<TagExpr>, Box<TagExpr>),
+}
+
+impl TagExpr {
+    /// Parses an expression where NOT binds tighter than AND, and AND
+    /// tighter than OR. The operators can be written in lowercase too.
+    pub fn parse(expression: &str) -> anyhow::Result<TagExpr> {
+        let spaced = expression.replace('(', " ( ").replace(')', " ) ");
+        let tokens: Vec<&str> = spaced.split_whitespace().collect();
+        if tokens.is_empty() {
+            bail!("The tag expression is empty");
+        }
+
+        let mut parser = Parser { tokens, position: 0 };
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            bail!("Unexpected '{}' in the tag expression", token);
+        }
+
+        Ok(expr)
+    }
+
+    pub fn matches(&self, tags: &[String]) -> bool {
+        match self {
+            TagExpr::Tag(tag) => tags.iter().any(|command_tag| is_within(command_tag, tag)),
+            TagExpr::Not(expr) => !expr.matches(tags),
+            TagExpr::And(left, right) => left.matches(tags) && right.matches(tags),
+            TagExpr::Or(left, right) => left.matches(tags) || right.matches(tags),
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn is_next(&self, operator: &str) -> bool {
+        self.peek().is_some_and(|token| token.eq_ignore_ascii_case(operator))
+    }
+
+    fn or(&mut self) -> anyhow::Result<TagExpr> {
+        let mut expr = self.and()?;
+        while self.is_next("OR") {
+            self.position += 1;
+            expr = TagExpr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> anyhow::Result<TagExpr> {
+        let mut expr = self.not()?;
+        while self.is_next("AND") {
+            self.position += 1;
+            expr = TagExpr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> anyhow::Result<TagExpr> {
+        let Some(token) = self.peek() else { bail!("The tag expression ends too early") };
+        self.position += 1;
+
+        match token {
+            _ if token.eq_ignore_ascii_case("NOT") => Ok(TagExpr::Not(Box::new(self.not()?))),
+            "(" => {
+                let expr = self.or()?;
+                if self.peek() != Some(")") {
+                    bail!("Missing ')' in the tag expression");
+                }
+                self.position += 1;
+                Ok(expr)
+            },
+            ")" => bail!("Unexpected ')' in the tag expression"),
+            _ if token.eq_ignore_ascii_case("AND") || token.eq_ignore_ascii_case("OR") => {
+                bail!("Expected a tag before '{}' in the tag expression", token)
+            },
+            tag => Ok(TagExpr::Tag(tag.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn tag_expressions_follow_the_operator_precedence() {
+        let expr = TagExpr::parse("git AND NOT basics OR docker").unwrap();
+
+        assert!(expr.matches(&tags(&["git"])));
+        assert!(!expr.matches(&tags(&["git", "basics"])));
+        assert!(expr.matches(&tags(&["docker", "basics"])));
+
+        let expr = TagExpr::parse("git and not (basics or config)").unwrap();
+        assert!(!expr.matches(&tags(&["git", "config"])));
+
+        assert!(TagExpr::parse("git AND").is_err());
+        assert!(TagExpr::parse("(git").is_err());
+        assert!(TagExpr::parse("git docker").is_err());
+    }
+}
@@ -0,0 +1,189 @@
+/// How close an answer is to the expected commands.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Grade {
+    Exact,
+    /// Off by a few characters from `expected`, most likely a typo
+    NearMiss { expected: String },
+    /// `expected` is the closest expected command
+    Wrong { expected: String },
+}
+
+impl Grade {
+    pub fn is_correct(&self) -> bool {
+        matches!(self, Grade::Exact)
+    }
+}
+
+/// A piece of the comparison between an answer and an expected command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffPart {
+    Same(String),
+    /// Typed, but not expected
+    Extra(String),
+    /// Expected, but not typed
+    Missing(String),
+}
+
+/// Compares `answer` with every expected command. Differences in whitespace
+/// between tokens are ignored, and answers within a few typos of a command
+/// are near misses.
+pub fn grade(answer: &str, commands: &[String]) -> Grade {
+    let answer = normalize(answer);
+
+    let closest = commands.iter()
+        .map(|command| (distance(&answer, &normalize(command)), command))
+        .min_by_key(|(distance, _)| *distance);
+
+    match closest {
+        None => Grade::Wrong { expected: String::new() },
+        Some((0, _)) => Grade::Exact,
+        Some((_, command)) if is_near_miss(&answer, &normalize(command)) => Grade::NearMiss { expected: command.trim().to_string() },
+        Some((_, command)) => Grade::Wrong { expected: command.trim().to_string() },
+    }
+}
+
+/// Whether the normalized `answer` is only a few typos away from `command`.
+/// The program has to be the same, and so do short flags: `-r` is not a
+/// typo of `-f`.
+fn is_near_miss(answer: &str, command: &str) -> bool {
+    let answer_tokens: Vec<&str> = answer.split(' ').collect();
+    let command_tokens: Vec<&str> = command.split(' ').collect();
+
+    if answer_tokens[0] != command_tokens[0] || distance(answer, command) > max_typos(command) {
+        return false;
+    }
+
+    // Tokens can only be compared one by one when no space was mistyped
+    answer_tokens.len() != command_tokens.len() || answer_tokens.iter().zip(&command_tokens)
+        .all(|(answer, command)| !command.starts_with('-') || distance(answer, command) <= max_typos(command))
+}
+
+/// The characters to keep, remove and add to turn `answer` into `expected`.
+pub fn diff(answer: &str, expected: &str) -> Vec<DiffPart> {
+    let answer: Vec<char> = normalize(answer).chars().collect();
+    let expected: Vec<char> = normalize(expected).chars().collect();
+    let distances = distance_table(&answer, &expected, false);
+
+    let mut parts: Vec<DiffPart> = vec![];
+    let push = |parts: &mut Vec<DiffPart>, part: DiffPart| {
+        match (parts.last_mut(), part) {
+            (Some(DiffPart::Same(text)), DiffPart::Same(c))
+            | (Some(DiffPart::Extra(text)), DiffPart::Extra(c))
+            | (Some(DiffPart::Missing(text)), DiffPart::Missing(c)) => text.insert_str(0, &c),
+            (_, part) => parts.push(part),
+        }
+    };
+
+    // Walks the table back from the end, so the parts are built in reverse
+    let (mut i, mut j) = (answer.len(), expected.len());
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 && answer[i - 1] == expected[j - 1] && distances[i][j] == distances[i - 1][j - 1] {
+            push(&mut parts, DiffPart::Same(answer[i - 1].to_string()));
+            i -= 1;
+            j -= 1;
+        }
+        else if i > 0 && distances[i][j] == distances[i - 1][j] + 1 {
+            push(&mut parts, DiffPart::Extra(answer[i - 1].to_string()));
+            i -= 1;
+        }
+        else if j > 0 && distances[i][j] == distances[i][j - 1] + 1 {
+            push(&mut parts, DiffPart::Missing(expected[j - 1].to_string()));
+            j -= 1;
+        }
+        else {
+            push(&mut parts, DiffPart::Missing(expected[j - 1].to_string()));
+            push(&mut parts, DiffPart::Extra(answer[i - 1].to_string()));
+            i -= 1;
+            j -= 1;
+        }
+    }
+
+    parts.reverse();
+    parts
+}
+
+fn normalize(command: &str) -> String {
+    command.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// One typo is tolerated for every ten characters, up to three, and none
+/// below five characters, where a single change gives another command.
+fn max_typos(command: &str) -> usize {
+    match command.trim().chars().count() {
+        0..=4 => 0,
+        count => (count / 10).clamp(1, 3),
+    }
+}
+
+/// The number of insertions, deletions, substitutions and transpositions
+/// of adjacent characters needed to turn `a` into `b`.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    distance_table(&a, &b, true)[a.len()][b.len()]
+}
+
+/// The edit distances between every prefix of `a` and of `b`.
+fn distance_table(a: &[char], b: &[char], transpositions: bool) -> Vec<Vec<usize>> {
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            distances[i][j] = substitution.min(distances[i - 1][j] + 1).min(distances[i][j - 1] + 1);
+
+            if transpositions && i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distances[i][j] = distances[i][j].min(distances[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    distances
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(commands: &[&str]) -> Vec<String> {
+        commands.iter().map(|command| command.to_string()).collect()
+    }
+
+    #[test]
+    fn answers_are_graded_against_the_closest_command() {
+        let expected = commands(&["git status", "git status --short"]);
+
+        assert_eq!(grade("git  status ", &expected), Grade::Exact);
+        assert_eq!(grade("git stauts", &expected), Grade::NearMiss { expected: "git status".to_string() });
+        assert_eq!(grade("git status -short", &expected), Grade::NearMiss { expected: "git status --short".to_string() });
+        assert_eq!(grade("git log", &expected), Grade::Wrong { expected: "git status".to_string() });
+    }
+
+    #[test]
+    fn other_programs_and_flags_are_not_typos() {
+        assert_eq!(grade("ps", &commands(&["ls"])), Grade::Wrong { expected: "ls".to_string() });
+        assert_eq!(grade("rm -f", &commands(&["rm -r"])), Grade::Wrong { expected: "rm -r".to_string() });
+        assert_eq!(grade("git pull", &commands(&["git push"])), Grade::Wrong { expected: "git push".to_string() });
+        assert_eq!(grade("tar -xf logs.tar", &commands(&["tar -cf logs.tar"])), Grade::Wrong { expected: "tar -cf logs.tar".to_string() });
+        assert_eq!(grade("kubectl get pod", &commands(&["kubecl get pod"])), Grade::Wrong { expected: "kubecl get pod".to_string() });
+        assert_eq!(grade("kubectl get pdos", &commands(&["kubectl get pods"])), Grade::NearMiss { expected: "kubectl get pods".to_string() });
+    }
+
+    #[test]
+    fn diff_shows_the_extra_and_missing_characters() {
+        assert_eq!(diff("git statsu", "git status"), vec![
+            DiffPart::Same("git stat".to_string()),
+            DiffPart::Missing("u".to_string()),
+            DiffPart::Same("s".to_string()),
+            DiffPart::Extra("u".to_string()),
+        ]);
+    }
+}
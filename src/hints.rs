@@ -0,0 +1,68 @@
+use super::{CardKind, Command};
+
+/// The hints of a command, from the least to the most revealing: its clues,
+/// the program name, the first tokens of the answer and the whole answer
+/// with its letters and digits masked. Cloze cards already show the
+/// command around their gaps, so only the masked gaps are hinted.
+pub fn hint_ladder(command: &Command) -> Vec<String> {
+    let mut hints = vec![];
+
+    if let Some(clues) = &command.clues {
+        if !clues.trim().is_empty() {
+            hints.push(clues.trim().to_string());
+        }
+    }
+
+    let Some(answer) = command.commands.first().map(|answer| answer.trim()) else { return hints };
+    let tokens: Vec<&str> = answer.split_whitespace().collect();
+
+    if command.kind != CardKind::Cloze {
+        if let Some(program) = tokens.first() {
+            hints.push(format!("Program: {}", program));
+        }
+        if tokens.len() > 2 {
+            hints.push(format!("Starts with: {} {}", tokens[0], tokens[1]));
+        }
+    }
+    hints.push(format!("Answer: {}", mask(answer)));
+
+    hints
+}
+
+/// Replaces letters and digits with underscores, keeping spaces and symbols.
+fn mask(answer: &str) -> String {
+    answer.chars()
+        .map(|c| if c.is_alphanumeric() { '_' } else { c })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hints_reveal_the_answer_progressively() {
+        let command: Command = toml::from_str(r#"
+            task = "Show the history in one line per commit"
+            commands = ["git log --oneline -n 5"]
+        "#).unwrap();
+
+        assert_eq!(hint_ladder(&command), vec![
+            "Program: git",
+            "Starts with: git log",
+            "Answer: ___ ___ --_______ -_ _",
+        ]);
+    }
+
+    #[test]
+    fn cloze_gaps_are_only_masked() {
+        let command: Command = toml::from_str(r#"
+            task = "Delete the old logs"
+            commands = ["find . -name '*.log' {{c1::-exec rm {} +}}"]
+            type = "cloze"
+        "#).unwrap();
+        let siblings = crate::cards::cloze_siblings(&command);
+
+        assert_eq!(hint_ladder(&siblings[0]), vec!["Answer: -____ __ {} +"]);
+    }
+}
@@ -1,32 +1,90 @@
 mod api;
+mod cards;
+mod challenge;
 mod database;
+mod filters;
+mod grading;
+mod hints;
+mod markdown;
+mod profiles;
+mod prompt;
+mod session;
+mod tui;
 mod spaced_repetition;
+mod stats;
+mod tags;
 pub mod utilities;
 use clap::{Parser, Subcommand};
-use serde_derive::Deserialize;
+use serde_derive::{Deserialize, Serialize};
 use dotenv::dotenv;
 use chrono::{DateTime, Utc};
-use utilities::get_current_date;
-use api::{review, practice, show_commands};
+use utilities::{get_current_date, ExportFormat, ImportFormat};
+use prompt::PromptOptions;
+use filters::PracticeOptions;
+use api::{review, review_reverse, practice, cram, challenge, import, show_commands, show_search_results};
 
-#[derive(sqlx::Type, Debug, Clone)]
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, PartialEq, clap::ValueEnum)]
 pub enum Mode {
     New,
     Learning,
     Failed
 }
 
-impl ToString for Mode {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-           Mode::New => String::from("New"), 
-           Mode::Learning => String::from("Learning"), 
-           Mode::Failed => String::from("Failed"), 
+           Mode::New => write!(f, "New"), 
+           Mode::Learning => write!(f, "Learning"), 
+           Mode::Failed => write!(f, "Failed"), 
         }
     }
 }
 
-#[derive(sqlx::Type, Debug, Clone)]
+/// How a command is asked during a review.
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CardKind {
+    /// Type the command for the task
+    #[default]
+    Recall,
+    /// Choose the command for the task among commands of other cards with the same tag
+    MultipleChoice,
+    /// Choose or type the task of a command
+    Reverse,
+    /// Explain what a flag does in the command, graded by the user
+    Explain,
+    /// Fill a gap of a command, marked as `{{c1::-mtime +7}}`
+    Cloze,
+    /// Type the command of each step, in order
+    Scenario,
+}
+
+impl CardKind {
+    fn is_recall(&self) -> bool {
+        *self == CardKind::Recall
+    }
+
+    /// Whether the answer is a command typed by the user.
+    pub fn is_typed(&self) -> bool {
+        matches!(self, CardKind::Recall | CardKind::Cloze)
+    }
+}
+
+impl std::fmt::Display for CardKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CardKind::Recall => write!(f, "Recall"),
+            CardKind::MultipleChoice => write!(f, "MultipleChoice"),
+            CardKind::Reverse => write!(f, "Reverse"),
+            CardKind::Explain => write!(f, "Explain"),
+            CardKind::Cloze => write!(f, "Cloze"),
+            CardKind::Scenario => write!(f, "Scenario"),
+        }
+    }
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct SRData {
     pub created: DateTime<Utc>,
     pub last_review: Option<DateTime<Utc>>,
@@ -51,26 +109,69 @@ impl Default for SRData {
     }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Command {
+    #[serde(skip_serializing)]
     pub id: Option<i64>,
     pub task: String,
     pub clues: Option<String>,
     pub context: Option<String>, 
     pub prompt: Option<String>,
+    /// Scenario cards have steps instead
+    #[serde(default)]
     pub commands: Vec<String>,
     pub response: Option<String>,
     pub extra: Option<String>,
     pub tags: Option<Vec<String>>,
-    #[serde(skip)]
+    /// Directory whose files are completed at the answer prompt
+    pub fixtures: Option<String>,
+    #[serde(rename = "type", default, skip_serializing_if = "CardKind::is_recall")]
+    pub kind: CardKind,
+    /// The flag to explain in `Explain` cards
+    pub flag: Option<String>,
+    /// The whole command of a `Cloze` card, with the gaps to fill marked. It
+    /// can be given instead of `commands`, which then have the answer
+    pub cloze: Option<String>,
+    /// Commands of the same group are siblings, see `recli bury`
+    pub group: Option<String>,
+    /// The steps of a `Scenario` card, asked in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<Step>,
+    #[serde(rename = "progress", default, skip_serializing)]
     pub sr_data: SRData
 }
 
+/// A step of a `Scenario` card.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Step {
+    pub task: String,
+    pub commands: Vec<String>,
+    pub response: Option<String>,
+}
+
+impl SRData {
+    /// Whether the command has to be reviewed on `date`. New and failed
+    /// commands are always due.
+    pub fn is_due_on(&self, date: DateTime<Utc>) -> bool {
+        match self.due_date() {
+            Some(due_date) => date >= due_date,
+            None => true,
+        }
+    }
+
+    /// The date of the next review of a learned command, `None` if it is
+    /// always due.
+    pub fn due_date(&self) -> Option<DateTime<Utc>> {
+        match (&self.mode, self.last_review) {
+            (Mode::Learning, Some(last_review)) => Some(last_review + chrono::Duration::days(self.interval)),
+            _ => None,
+        }
+    }
+}
+
 impl Command {
     fn is_pending(&self) -> bool {
-        let current = get_current_date();
-        let duration = current - self.sr_data.last_review.unwrap();
-        duration.num_days() >= self.sr_data.interval
+        self.sr_data.is_due_on(get_current_date())
     }
 }
 
@@ -79,6 +180,9 @@ impl Command {
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
 struct Cli {
+    /// Use this profile instead of RECLI_PROFILE or the one chosen with 'recli profile use'
+    #[arg(long, global = true, value_name = "NAME")]
+    profile: Option<String>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -86,22 +190,131 @@ struct Cli {
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Review commands schedule for today
-    Review,
-    /// Add new commands from a toml file
+    Review {
+        /// Use the full-screen interface
+        #[arg(long)]
+        tui: bool,
+        /// Show the commands and explain what they do, scheduled apart from typing them
+        #[arg(long, conflicts_with = "tui")]
+        reverse: bool,
+        #[command(flatten)]
+        prompt: PromptOptions,
+    },
+    /// Add new commands from toml, json, yaml, json lines or markdown files
     Import {
-        #[arg(value_name = "FILE")]
-        file_path: String
+        /// A deck file, a directory or a glob pattern
+        #[arg(value_name = "PATH")]
+        path: String,
+        /// Format of the file, detected from its extension by default
+        #[arg(short, long, value_enum)]
+        format: Option<ImportFormat>,
+        /// Show the commands that would be imported without saving them
+        #[arg(long)]
+        dry_run: bool,
     },
-    /// Practice commands
+    /// Practice commands, without changing their schedule
     Practice {
+        /// A tag or an expression of tags, such as 'git AND NOT basics'
+        #[arg(value_name = "TAGS")]
+        tags: Vec<String>,
+        #[command(flatten)]
+        filters: PracticeOptions,
+        #[command(flatten)]
+        prompt: PromptOptions,
+    },
+    /// Review the siblings of commands with a tag on different days: commands
+    /// in the same group, or whose answers start with the same tokens
+    Bury {
         #[arg(value_name = "TAG")]
-        tag: String
+        tag: String,
+        /// How many first tokens of the answers are compared
+        #[arg(long, default_value_t = 2)]
+        tokens: usize,
+        /// Stop burying the siblings of commands with the tag
+        #[arg(long)]
+        off: bool,
+    },
+    /// List the suspended commands, or review some of them again with --id
+    Unsuspend {
+        /// A command to review again, by its id in the list
+        #[arg(long = "id", value_name = "ID")]
+        ids: Vec<i64>,
+    },
+    /// Drill commands regardless of their due date, without changing their schedule
+    Cram {
+        /// A tag or an expression of tags, such as 'git AND NOT basics'
+        #[arg(value_name = "TAGS")]
+        tags: Vec<String>,
+        /// Correct answers in a row needed to finish a command
+        #[arg(long, default_value_t = 2)]
+        streak: usize,
+        #[command(flatten)]
+        filters: PracticeOptions,
+        #[command(flatten)]
+        prompt: PromptOptions,
+    },
+    /// Answer random commands against the clock, scored by their difficulty
+    Challenge {
+        #[arg(short, long, value_name = "TAG")]
+        tag: Option<String>,
+        /// Length of the challenge in seconds
+        #[arg(short, long, default_value_t = 120)]
+        duration: u64,
+        /// Only show the leaderboard of the tag, or of the challenges with all commands without one
+        #[arg(long)]
+        leaderboard: bool,
+        #[command(flatten)]
+        prompt: PromptOptions,
+    },
+    /// Manage the tags of the commands. Tags can be nested, as in
+    /// 'k8s::kubectl::logs', and a tag includes its children
+    Tag {
+        #[command(subcommand)]
+        action: tags::TagAction,
+    },
+    /// Manage the profiles, each with a database of its own. RECLI_DB
+    /// can point to a database outside of them
+    Profile {
+        #[command(subcommand)]
+        action: profiles::ProfileAction,
+    },
+    /// Search the text of the commands
+    Search {
+        /// The words to find, after the options since they can start with -
+        #[arg(value_name = "QUERY", required = true, allow_hyphen_values = true)]
+        query: Vec<String>,
+        /// Practice the commands found
+        #[arg(long)]
+        practice: bool,
+        /// Add a tag to the commands found
+        #[arg(long, value_name = "TAG")]
+        tag: Option<String>,
+        #[command(flatten)]
+        prompt: PromptOptions,
     },
     /// Show saved commands
     Show { 
         #[arg(value_name = "TAG")]
         tag: Option<String>
     },
+    /// Show statistics about the saved commands and the reviews
+    Stats {
+        #[arg(short, long, value_name = "TAG")]
+        tag: Option<String>,
+    },
+    /// Export saved commands to a file
+    Export {
+        #[arg(short, long, value_name = "TAG")]
+        tag: Option<String>,
+        #[arg(short, long, value_enum, default_value_t = ExportFormat::Toml)]
+        format: ExportFormat,
+        /// Include the scheduling state of each command
+        #[arg(long)]
+        with_progress: bool,
+        /// Write to a file instead of the standard output
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<String>,
+    },
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -109,20 +322,88 @@ async fn main() -> anyhow::Result<()> {
     dotenv().ok(); 
 
     let cli = Cli::parse();
-    database::create_if_not_exists().await?;
+    profiles::select(cli.profile.clone())?;
+    if !matches!(cli.command, Commands::Profile { .. }) {
+        profiles::check_active()?;
+        database::create_if_not_exists().await?;
+    }
 
     match &cli.command {
-        Commands::Review => {
-            let mut commands = database::find_today_commands().await?;
-            review(&mut commands).await?;
+        Commands::Review { reverse: true, prompt, .. } => {
+            let mut commands = database::find_reverse_commands().await?;
+            review_reverse(&mut commands, prompt).await?;
+        },
+        Commands::Review { tui, prompt, .. } => {
+            let (mut session, mut commands) = session::start_review().await?;
+            if *tui {
+                tui::review(&mut session, &mut commands).await?;
+            }
+            else {
+                review(&mut session, &mut commands, prompt).await?;
+            }
+        },
+        Commands::Import { path, format, dry_run } =>  {
+            import(path, format.as_ref(), *dry_run).await?;
+        },
+        Commands::Practice { tags, filters, prompt } => {
+            let mut commands = filters.find_commands(tags).await?;
+            practice(&mut commands, prompt, filters.time_limit()).await?;
+        },
+        Commands::Cram { tags, streak, filters, prompt } => {
+            let mut commands = filters.find_commands(tags).await?;
+            cram(&mut commands, prompt, filters.time_limit(), *streak).await?;
         },
-        Commands::Import { file_path } =>  {
-            let commands = utilities::get_commands_from_toml(&file_path);
-            database::save_commands(&commands).await?; 
+        Commands::Unsuspend { ids } if ids.is_empty() => {
+            let suspended = database::find_suspended().await?;
+            let commands: Vec<Command> = database::find_all_commands().await?.into_iter()
+                .filter(|command| command.id.is_some_and(|id| suspended.contains(&id)))
+                .collect();
+            show_commands(&commands);
         },
-        Commands::Practice { tag } => {
-            let mut commands = database::find_commands_with_tag(tag).await?;
-            practice(&mut commands).await?;
+        Commands::Unsuspend { ids } => {
+            let unsuspended = database::unsuspend_commands(ids).await?;
+            println!("RECLI: {} of {} commands will be reviewed again.", unsuspended, ids.len());
+        },
+        Commands::Bury { tag, tokens, off } => {
+            database::save_sibling_rule(tag, (!*off).then_some(*tokens)).await?;
+            match off {
+                true => println!("RECLI: Siblings of commands tagged {} are no longer buried.", tag),
+                false => println!("RECLI: Siblings of commands tagged {} are reviewed on different days.", tag),
+            }
+        },
+        Commands::Challenge { tag, duration, leaderboard, prompt } => {
+            if *leaderboard {
+                let runs = database::find_challenge_runs(tag.as_deref()).await?;
+                challenge::show_leaderboard(tag.as_deref(), &runs, None);
+            }
+            else {
+                let commands = match tag {
+                    Some(tag) => database::find_commands_with_tag(tag).await?,
+                    None => database::find_all_commands().await?
+                };
+                let duration = std::time::Duration::from_secs(*duration);
+                challenge(Vec::from(commands), tag.as_deref(), duration, prompt).await?;
+            }
+        },
+        Commands::Tag { action } => {
+            tags::manage(action).await?;
+        },
+        Commands::Profile { action } => {
+            profiles::manage(action).await?;
+        },
+        Commands::Search { query, practice: practice_found, tag, prompt } => {
+            let results = database::search_commands(&query.join(" ")).await?;
+            show_search_results(&results);
+
+            let ids: Vec<i64> = results.iter().filter_map(|(command, _)| command.id).collect();
+            if let (Some(tag), false) = (tag, ids.is_empty()) {
+                let added = database::add_tag(&ids, tag).await?;
+                println!("RECLI: Tagged {} of {} commands with {}.", added, ids.len(), tag);
+            }
+            if *practice_found {
+                let mut commands = results.into_iter().map(|(command, _)| command).collect();
+                practice(&mut commands, prompt, None).await?;
+            }
         },
         Commands::Show { tag } => {
             let commands = match tag {
@@ -132,6 +413,22 @@ async fn main() -> anyhow::Result<()> {
             let vec: Vec<Command> = Vec::from(commands);
             show_commands(&vec);
         },
+        Commands::Stats { tag } => {
+            stats::show_stats(tag.as_deref()).await?;
+        },
+        Commands::Export { tag, format, with_progress, output } => {
+            let commands = match tag {
+                Some(tag) => database::find_commands_with_tag(tag).await?,
+                None => database::find_all_commands().await?
+            };
+            let vec: Vec<Command> = Vec::from(commands);
+            let exported = utilities::export_commands(&vec, format, *with_progress)?;
+
+            match output {
+                Some(output) => std::fs::write(output, exported)?,
+                None => print!("{exported}"),
+            }
+        },
     }
 
     Ok(())
@@ -0,0 +1,211 @@
+use crate::tags;
+use crate::utilities::ImportError;
+use crate::{CardKind, Command, SRData};
+
+/// Builds commands from a Markdown cheat sheet. Every fenced code block
+/// becomes a command whose task is the text written before it, and whose
+/// accepted answers are the non-empty lines of the block. Blocks right
+/// after another one, without a text of their own, add answers to its
+/// command. The headings above a block become a nested tag, and the
+/// blockquotes right after it become its extra information.
+pub fn get_commands_from_markdown(filename: &str, contents: &str) -> Result<Vec<Command>, ImportError> {
+    let mut commands: Vec<Command> = vec![];
+    let mut headings: Vec<(usize, String)> = vec![];
+    let mut description: Vec<String> = vec![];
+    let mut code_block: Option<(usize, Vec<String>)> = None;
+    let mut can_add_extra = false;
+
+    for (index, line) in contents.lines().enumerate() {
+        let trimmed = line.trim();
+
+        if let Some((start, lines)) = &mut code_block {
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                let answers: Vec<String> = lines.iter()
+                    .map(|line| line.trim().to_string())
+                    .filter(|line| !line.is_empty())
+                    .collect();
+
+                if description.is_empty() && can_add_extra {
+                    if let Some(command) = commands.last_mut() {
+                        for answer in answers {
+                            if !command.commands.contains(&answer) {
+                                command.commands.push(answer);
+                            }
+                        }
+                    }
+                    code_block = None;
+                    continue;
+                }
+                if description.is_empty() {
+                    return Err(ImportError {
+                        line: Some(*start),
+                        ..ImportError::new(filename, "Code block without a description")
+                    });
+                }
+
+                if !answers.is_empty() {
+                    commands.push(Command {
+                        id: None,
+                        task: description.join(" "),
+                        clues: None,
+                        context: None,
+                        prompt: None,
+                        commands: answers,
+                        response: None,
+                        extra: None,
+                        tags: Some(headings_to_tag(&headings).into_iter().collect()),
+                        fixtures: None,
+                        kind: CardKind::Recall,
+                        flag: None,
+                        cloze: None,
+                        group: None,
+                        steps: vec![],
+                        sr_data: SRData::default(),
+                    });
+                    can_add_extra = true;
+                }
+                description.clear();
+                code_block = None;
+            }
+            else {
+                lines.push(line.to_string());
+            }
+            continue;
+        }
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            code_block = Some((index + 1, vec![]));
+        }
+        else if let Some(heading) = parse_heading(trimmed) {
+            headings.retain(|(level, _)| *level < heading.0);
+            headings.push(heading);
+            description.clear();
+            can_add_extra = false;
+        }
+        else if let Some(quote) = trimmed.strip_prefix('>') {
+            if !can_add_extra { continue; }
+
+            if let Some(command) = commands.last_mut() {
+                let extra = command.extra.get_or_insert_with(String::new);
+                if !extra.is_empty() {
+                    extra.push('\n');
+                }
+                extra.push_str(quote.trim());
+            }
+        }
+        else if !trimmed.is_empty() {
+            description.push(strip_list_marker(trimmed).to_string());
+            can_add_extra = false;
+        }
+    }
+
+    if let Some((start, _)) = code_block {
+        return Err(ImportError {
+            line: Some(start),
+            ..ImportError::new(filename, "Unclosed code block")
+        });
+    }
+
+    Ok(commands)
+}
+
+fn parse_heading(line: &str) -> Option<(usize, String)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if level == 0 { return None; }
+
+    let text = line[level..].strip_prefix(' ')?.trim().trim_end_matches('#').trim();
+    if text.is_empty() { return None; }
+
+    Some((level, text.to_string()))
+}
+
+/// The headings above a block as a nested tag, such as `git::branches`.
+fn headings_to_tag(headings: &[(usize, String)]) -> Option<String> {
+    if headings.is_empty() {
+        return None;
+    }
+
+    Some(headings.iter()
+        .map(|(_, heading)| heading_to_tag(heading))
+        .collect::<Vec<String>>()
+        .join(tags::SEPARATOR))
+}
+
+fn heading_to_tag(heading: &str) -> String {
+    heading.split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
+fn strip_list_marker(line: &str) -> &str {
+    for marker in ["- ", "* ", "+ "] {
+        if let Some(stripped) = line.strip_prefix(marker) {
+            return stripped.trim();
+        }
+    }
+
+    match line.split_once(". ") {
+        Some((number, stripped)) if number.chars().all(|c| c.is_ascii_digit()) => stripped.trim(),
+        _ => line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::markdown::get_commands_from_markdown;
+
+    #[test]
+    fn code_blocks_become_commands() {
+        let contents = "\
+# Git
+## Branches
+- Create a branch and switch to it
+```sh
+git switch -c feature
+git checkout -b feature
+```
+> `-c` stands for create.
+
+Delete a merged branch
+```
+git branch -d feature
+```
+";
+        let commands = get_commands_from_markdown("git.md", contents).unwrap();
+
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].task, "Create a branch and switch to it");
+        assert_eq!(commands[0].commands, vec!["git switch -c feature", "git checkout -b feature"]);
+        assert_eq!(commands[0].tags, Some(vec![String::from("git::branches")]));
+        assert_eq!(commands[0].extra.as_deref(), Some("`-c` stands for create."));
+        assert_eq!(commands[1].extra, None);
+    }
+
+    #[test]
+    fn following_code_blocks_add_answers() {
+        let contents = "\
+# Git
+Undo the last commit
+```
+git reset HEAD~
+```
+
+```
+git reset --soft HEAD~
+```
+";
+        let commands = get_commands_from_markdown("git.md", contents).unwrap();
+
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].commands, vec!["git reset HEAD~", "git reset --soft HEAD~"]);
+        assert!(get_commands_from_markdown("git.md", "# Git\n```\ngit status\n```\n").is_err());
+    }
+
+    #[test]
+    fn unclosed_code_block_is_an_error() {
+        let error = get_commands_from_markdown("git.md", "Status\n```\ngit status\n").unwrap_err();
+
+        assert_eq!(error.line, Some(2));
+    }
+}
@@ -0,0 +1,271 @@
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use anyhow::bail;
+use comfy_table::{Cell, Color};
+use crate::api::new_table;
+use super::database;
+
+/// The profile of the database at the root of the data directory.
+const DEFAULT_PROFILE: &str = "default";
+
+/// The profile given with `--profile`, which overrides the environment.
+static SELECTED: OnceLock<Option<String>> = OnceLock::new();
+
+#[cfg(test)]
+thread_local! {
+    /// The database of the test running on this thread, so that tests
+    /// running in parallel don't share one.
+    static TEST_DATABASE: std::cell::RefCell<Option<PathBuf>> = const { std::cell::RefCell::new(None) };
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum ProfileAction {
+    /// List the profiles with their number of commands
+    List,
+    /// Create a profile with an empty database
+    Create {
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+    /// Use a profile from now on, 'default' to go back to the default one
+    Use {
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+    /// Remove a profile and its database
+    Remove {
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+}
+
+/// Selects the profile given with `--profile` and checks the name of the
+/// profile in use.
+pub fn select(profile: Option<String>) -> anyhow::Result<()> {
+    SELECTED.set(profile).expect("The profile is selected once");
+
+    match active() {
+        Some(name) => check_name(&name),
+        None => Ok(()),
+    }
+}
+
+/// The profile in use: the one given with `--profile`, else `RECLI_PROFILE`,
+/// else the one chosen with 'recli profile use'. `None` when `RECLI_DB`
+/// points to a database of its own instead.
+pub fn active() -> Option<String> {
+    if let Some(name) = SELECTED.get().cloned().flatten() {
+        return Some(name);
+    }
+    if env_var("RECLI_DB").is_some() {
+        return None;
+    }
+
+    let saved = std::fs::read_to_string(data_directory().join("profile")).ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+
+    Some(env_var("RECLI_PROFILE").or(saved).unwrap_or(DEFAULT_PROFILE.to_string()))
+}
+
+/// The database of the profile in use, or `RECLI_DB`.
+pub fn database_path() -> PathBuf {
+    #[cfg(test)]
+    if let Some(path) = TEST_DATABASE.with(|path| path.borrow().clone()) {
+        return path;
+    }
+
+    let path = match active() {
+        Some(name) => profile_path(&name),
+        None => PathBuf::from(env_var("RECLI_DB").unwrap_or_default()),
+    };
+
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).expect("Failed to create data directory");
+    }
+
+    path
+}
+
+/// Makes the database functions called from this thread use `path`.
+#[cfg(test)]
+pub fn use_test_database(path: PathBuf) {
+    TEST_DATABASE.with(|test_database| *test_database.borrow_mut() = Some(path));
+}
+
+/// Fails if the profile in use was never created, so that a mistyped
+/// profile does not start an empty database.
+pub fn check_active() -> anyhow::Result<()> {
+    match active() {
+        Some(name) if name != DEFAULT_PROFILE && !profile_path(&name).exists() => {
+            bail!("The profile {} does not exist, create it with 'recli profile create {}'", name, name)
+        },
+        _ => Ok(()),
+    }
+}
+
+/// `$XDG_DATA_HOME/recli`, unless `~/.recli` was already used before
+/// `XDG_DATA_HOME` was honored.
+fn data_directory() -> PathBuf {
+    let home = match std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) {
+        Some(home) => PathBuf::from(home),
+        // Fallback to the current directory if neither HOME nor USERPROFILE is set
+        None => PathBuf::from("."),
+    };
+    let legacy = home.join(".recli");
+
+    match env_var("XDG_DATA_HOME") {
+        Some(data_home) if !legacy.exists() => PathBuf::from(data_home).join("recli"),
+        _ => legacy,
+    }
+}
+
+fn profile_path(name: &str) -> PathBuf {
+    match name {
+        DEFAULT_PROFILE => data_directory().join("recli.db"),
+        name => data_directory().join("profiles").join(format!("{}.db", name)),
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn check_name(name: &str) -> anyhow::Result<()> {
+    if !is_valid_name(name) {
+        bail!("Invalid profile {}: names can only have letters, digits, '-' and '_'", name);
+    }
+    Ok(())
+}
+
+/// The created profiles, the default one first.
+fn find_profiles() -> anyhow::Result<Vec<String>> {
+    let mut names = vec![DEFAULT_PROFILE.to_string()];
+
+    if let Ok(entries) = std::fs::read_dir(data_directory().join("profiles")) {
+        let mut created: Vec<String> = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "db") {
+                created.extend(path.file_stem().map(|stem| stem.to_string_lossy().to_string()));
+            }
+        }
+        created.sort();
+        names.extend(created.into_iter().filter(|name| is_valid_name(name) && name != DEFAULT_PROFILE));
+    }
+
+    Ok(names)
+}
+
+pub async fn manage(action: &ProfileAction) -> anyhow::Result<()> {
+    match action {
+        ProfileAction::List => show_profiles().await?,
+        ProfileAction::Create { name } => {
+            check_name(name)?;
+            let path = profile_path(name);
+            if name == DEFAULT_PROFILE || path.exists() {
+                bail!("The profile {} already exists", name);
+            }
+
+            std::fs::create_dir_all(path.parent().expect("Profiles are in the data directory"))?;
+            database::create_database(&path.to_string_lossy()).await?;
+            println!("RECLI: Created the profile {}, use it with 'recli profile use {}' or --profile {}.", name, name, name);
+        },
+        ProfileAction::Use { name } => {
+            check_name(name)?;
+            let saved = data_directory().join("profile");
+            if name == DEFAULT_PROFILE {
+                if saved.exists() {
+                    std::fs::remove_file(saved)?;
+                }
+            }
+            else if profile_path(name).exists() {
+                std::fs::create_dir_all(data_directory())?;
+                std::fs::write(saved, name)?;
+            }
+            else {
+                bail!("The profile {} does not exist, create it with 'recli profile create {}'", name, name);
+            }
+
+            println!("RECLI: Using the profile {}.", name);
+            if env_var("RECLI_PROFILE").is_some() || env_var("RECLI_DB").is_some() {
+                println!("RECLI: RECLI_PROFILE or RECLI_DB is set and takes precedence over it.");
+            }
+        },
+        ProfileAction::Remove { name } => {
+            check_name(name)?;
+            let path = profile_path(name);
+            if name == DEFAULT_PROFILE {
+                bail!("The default profile can't be removed");
+            }
+            if !path.exists() {
+                bail!("The profile {} does not exist", name);
+            }
+            if active().as_deref() == Some(name) {
+                bail!("The profile {} is in use, switch to another one first", name);
+            }
+
+            let count = database::count_commands(&path.to_string_lossy()).await?;
+            std::fs::remove_file(&path)?;
+            // SQLite's journal files, left when it was not closed cleanly
+            for suffix in ["-wal", "-shm", "-journal"] {
+                let journal = PathBuf::from(format!("{}{}", path.to_string_lossy(), suffix));
+                if journal.exists() {
+                    std::fs::remove_file(journal)?;
+                }
+            }
+            println!("RECLI: Removed the profile {} and its {} commands.", name, count);
+        },
+    }
+
+    Ok(())
+}
+
+/// Shows every profile with its number of commands, highlighting the one
+/// in use.
+async fn show_profiles() -> anyhow::Result<()> {
+    let active = active();
+    if active.is_none() {
+        println!("RECLI: Using the database in RECLI_DB, {}.", database_path().display());
+    }
+
+    let mut table = new_table(vec!["Profile", "Commands", "Database"]);
+    for name in find_profiles()? {
+        let path = profile_path(&name);
+        let count = match path.exists() {
+            true => database::count_commands(&path.to_string_lossy()).await?,
+            false => 0,
+        };
+        let (marker, color) = match active.as_deref() == Some(name.as_str()) {
+            true => ("* ", Color::Green),
+            false => ("  ", Color::Reset),
+        };
+
+        table.add_row(vec![
+            Cell::new(format!("{}{}", marker, name)).fg(color),
+            Cell::new(count),
+            Cell::new(path.display()),
+        ]);
+    }
+    println!("{table}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_names_are_file_names() {
+        assert!(is_valid_name("work-2"));
+        assert!(is_valid_name("k8s_exam"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("../recli"));
+        assert!(!is_valid_name("my profile"));
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{
+    Cmd, CompletionType, ConditionalEventHandler, Config, Context, DefaultEditor, EditMode, Editor,
+    Event, EventContext, EventHandler, Helper, KeyEvent, RepeatCount,
+};
+
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct PromptOptions {
+    /// Use vi keybindings at the answer prompt
+    #[arg(long)]
+    pub vi: bool,
+    /// Complete program names from $PATH at the answer prompt
+    #[arg(long)]
+    pub complete_programs: bool,
+}
+
+/// Completes file names inside the fixture directory of the current command
+/// and, if enabled, program names from `$PATH` for the first word.
+struct AnswerHelper {
+    fixtures: Option<PathBuf>,
+    programs: Vec<String>,
+}
+
+impl AnswerHelper {
+    fn complete_file(&self, word: &str) -> Vec<Pair> {
+        let Some(fixtures) = &self.fixtures else { return vec![] };
+
+        let (directory, prefix) = match word.rfind('/') {
+            Some(index) => word.split_at(index + 1),
+            None => ("", word),
+        };
+
+        let Ok(entries) = fs::read_dir(fixtures.join(directory)) else { return vec![] };
+
+        let mut candidates: Vec<Pair> = entries.flatten().filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with(prefix) { return None; }
+
+            let suffix = if entry.path().is_dir() { "/" } else { "" };
+            Some(Pair {
+                display: format!("{}{}", name, suffix),
+                replacement: format!("{}{}{}", directory, name, suffix),
+            })
+        }).collect();
+
+        candidates.sort_by(|a, b| a.display.cmp(&b.display));
+        candidates
+    }
+
+    fn complete_program(&self, word: &str) -> Vec<Pair> {
+        self.programs.iter()
+            .filter(|program| program.starts_with(word))
+            .map(|program| Pair {
+                display: program.clone(),
+                replacement: format!("{} ", program),
+            })
+            .collect()
+    }
+}
+
+impl Completer for AnswerHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = line[..pos].rfind(char::is_whitespace).map(|index| index + 1).unwrap_or(0);
+        let word = &line[start..pos];
+
+        let mut candidates = vec![];
+        if start == 0 && !word.contains('/') {
+            candidates = self.complete_program(word);
+        }
+        if candidates.is_empty() {
+            candidates = self.complete_file(word);
+        }
+
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for AnswerHelper {
+    type Hint = String;
+}
+
+impl Highlighter for AnswerHelper {}
+
+impl Validator for AnswerHelper {}
+
+impl Helper for AnswerHelper {}
+
+/// Interrupts the line editor after recording that the user asked to undo.
+struct UndoHandler(Arc<AtomicBool>);
+
+impl ConditionalEventHandler for UndoHandler {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, _ctx: &EventContext) -> Option<Cmd> {
+        self.0.store(true, Ordering::SeqCst);
+        Some(Cmd::Interrupt)
+    }
+}
+
+/// Interrupts the line editor after saving the line typed so far, so that
+/// it can be restored after showing a hint.
+struct HintHandler(Arc<Mutex<Option<String>>>);
+
+impl ConditionalEventHandler for HintHandler {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        *self.0.lock().unwrap() = Some(ctx.line().to_string());
+        Some(Cmd::Interrupt)
+    }
+}
+
+pub enum Answer {
+    Line(String),
+    /// The user pressed Ctrl+T to get a hint, with the line typed so far
+    Hint(String),
+    /// The user pressed Ctrl+Z to undo the last rating
+    Undo,
+    /// The user pressed Ctrl+C or Ctrl+D
+    Quit,
+}
+
+/// The line editor used to read answers in `review` and `practice`.
+pub struct AnswerPrompt {
+    editor: Editor<AnswerHelper, DefaultHistory>,
+    /// Reads the other lines, such as ratings, which are kept out of the history
+    plain: DefaultEditor,
+    undo: Arc<AtomicBool>,
+    hint: Arc<Mutex<Option<String>>>,
+}
+
+impl AnswerPrompt {
+    pub fn new(options: &PromptOptions) -> anyhow::Result<Self> {
+        let config = Config::builder()
+            .edit_mode(if options.vi { EditMode::Vi } else { EditMode::Emacs })
+            .completion_type(CompletionType::List)
+            .auto_add_history(true)
+            .build();
+
+        let mut editor = Editor::with_config(config)?;
+        editor.set_helper(Some(AnswerHelper {
+            fixtures: None,
+            programs: if options.complete_programs { find_programs() } else { vec![] },
+        }));
+
+        let undo = Arc::new(AtomicBool::new(false));
+        editor.bind_sequence(
+            KeyEvent::ctrl('Z'),
+            EventHandler::Conditional(Box::new(UndoHandler(undo.clone()))),
+        );
+
+        let hint = Arc::new(Mutex::new(None));
+        editor.bind_sequence(
+            KeyEvent::ctrl('T'),
+            EventHandler::Conditional(Box::new(HintHandler(hint.clone()))),
+        );
+
+        Ok(Self { editor, plain: DefaultEditor::new()?, undo, hint })
+    }
+
+    /// Reads an answer, starting with `initial` already typed.
+    pub fn read_answer(&mut self, prompt: &str, fixtures: Option<&str>, initial: &str) -> anyhow::Result<Answer> {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.fixtures = fixtures.map(PathBuf::from);
+        }
+
+        match self.editor.readline_with_initial(prompt, (initial, "")) {
+            Ok(line) => Ok(Answer::Line(line)),
+            Err(ReadlineError::Interrupted) if self.undo.swap(false, Ordering::SeqCst) => Ok(Answer::Undo),
+            Err(ReadlineError::Interrupted) if self.hint.lock().unwrap().is_some() => {
+                Ok(Answer::Hint(self.hint.lock().unwrap().take().unwrap_or_default()))
+            },
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => Ok(Answer::Quit),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Reads a line that is not an answer. `None` when the user pressed
+    /// Ctrl+C or Ctrl+D.
+    pub fn read_line(&mut self, prompt: &str) -> anyhow::Result<Option<String>> {
+        match self.plain.readline(prompt) {
+            Ok(line) => Ok(Some(line)),
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+fn find_programs() -> Vec<String> {
+    let Some(path) = std::env::var_os("PATH") else { return vec![] };
+
+    let mut programs = BTreeSet::new();
+    for directory in std::env::split_paths(&path) {
+        let Ok(entries) = fs::read_dir(&directory) else { continue };
+
+        for entry in entries.flatten() {
+            if is_executable(&entry.path()) {
+                programs.insert(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+    }
+
+    programs.into_iter().collect()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn helper(fixtures: Option<&Path>) -> AnswerHelper {
+        AnswerHelper {
+            fixtures: fixtures.map(Path::to_path_buf),
+            programs: vec!["git".to_string(), "grep".to_string(), "ls".to_string()],
+        }
+    }
+
+    fn complete(helper: &AnswerHelper, line: &str) -> (usize, Vec<String>) {
+        let history = DefaultHistory::new();
+        let (start, candidates) = helper.complete(line, line.len(), &Context::new(&history)).unwrap();
+        (start, candidates.into_iter().map(|pair| pair.replacement).collect())
+    }
+
+    #[test]
+    fn the_first_word_completes_programs() {
+        let helper = helper(None);
+
+        assert_eq!(complete(&helper, "g"), (0, vec!["git ".to_string(), "grep ".to_string()]));
+        assert_eq!(complete(&helper, "git s"), (4, vec![]));
+    }
+
+    #[test]
+    fn other_words_complete_fixture_files() {
+        let fixtures = tempfile::tempdir().unwrap();
+        fs::create_dir(fixtures.path().join("logs")).unwrap();
+        fs::write(fixtures.path().join("logs").join("app.log"), "").unwrap();
+        fs::write(fixtures.path().join("list.txt"), "").unwrap();
+        let helper = helper(Some(fixtures.path()));
+
+        assert_eq!(complete(&helper, "cat l"), (4, vec!["list.txt".to_string(), "logs/".to_string()]));
+        assert_eq!(complete(&helper, "cat logs/a"), (4, vec!["logs/app.log".to_string()]));
+        // A path is never a program
+        assert_eq!(complete(&helper, "logs/"), (0, vec!["logs/app.log".to_string()]));
+        // Without programs matching, the first word completes files too
+        assert_eq!(complete(&helper, "li"), (0, vec!["list.txt".to_string()]));
+    }
+}
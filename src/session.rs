@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use colored::*;
+use crate::utilities::get_current_date;
+use super::database;
+use super::{Command, Mode};
+
+/// The progress of a review. It is saved after every command so that an
+/// interrupted review can be resumed later in the same day.
+#[derive(Debug, Clone)]
+pub struct ReviewSession {
+    pub started: DateTime<Utc>,
+    pub original_size: usize,
+    pub reviewed: i64,
+    /// Ids of the commands that were failed and put back in the queue
+    pub requeued: Vec<i64>,
+}
+
+impl ReviewSession {
+    pub fn new(original_size: usize) -> Self {
+        Self {
+            started: Utc::now(),
+            original_size,
+            reviewed: 0,
+            requeued: vec![],
+        }
+    }
+
+    pub fn progress(&self, commands: &VecDeque<Command>) -> String {
+        format!("{}/{}", self.original_size.saturating_sub(commands.len()), self.original_size)
+    }
+
+    pub fn requeue(&mut self, command: &Command) {
+        if let Some(id) = command.id {
+            if !self.requeued.contains(&id) {
+                self.requeued.push(id);
+            }
+        }
+    }
+
+    pub fn print_summary(&self, remaining: usize) {
+        println!("{} Reviewed {} commands, {} failed and put back in the queue.",
+            "RECLI:".magenta().bold(),
+            self.reviewed,
+            self.requeued.len()
+        );
+
+        if remaining > 0 {
+            println!("{} commands remaining. Run 'recli review' to resume.", remaining.to_string().cyan());
+        }
+    }
+}
+
+/// The outcome of one answer, used to build the report shown at the end of
+/// a review or a practice.
+#[derive(Debug, Clone)]
+pub struct ReviewResult {
+    pub command_id: i64,
+    pub reviewed: DateTime<Utc>,
+    pub mode_before: Mode,
+    pub task: String,
+    pub tags: Vec<String>,
+    pub is_correct: bool,
+    pub response_time: Option<Duration>,
+    pub hints: usize,
+    pub e_factor_before: Option<f64>,
+    pub e_factor_after: Option<f64>,
+    /// Whether a learned command was failed and has to be learned again
+    pub lapsed: bool,
+    /// The tasks of the failed steps of a scenario card
+    pub failed_steps: Vec<String>,
+}
+
+/// Resumes the review saved today or, if there is none, starts a new one
+/// with the commands scheduled for today.
+pub async fn start_review() -> anyhow::Result<(ReviewSession, VecDeque<Command>)> {
+    if let Some((session, ids)) = database::find_review_session().await? {
+        if session.started.date_naive() == get_current_date().date_naive() {
+            let mut commands = VecDeque::new();
+            for id in ids {
+                if let Some(command) = database::find_command(id).await? {
+                    commands.push_back(command);
+                }
+            }
+
+            if !commands.is_empty() {
+                return Ok((session, commands));
+            }
+        }
+    }
+
+    let commands = database::find_today_commands().await?;
+    Ok((ReviewSession::new(commands.len()), commands))
+}
+
+/// Saves the session, or deletes it once there is nothing left to review.
+pub async fn save_review(session: &ReviewSession, commands: &VecDeque<Command>) -> anyhow::Result<()> {
+    if commands.is_empty() {
+        database::delete_review_session().await
+    }
+    else {
+        database::save_review_session(session, commands).await
+    }
+}
+
+/// Set when the user presses Ctrl+C, checked by the review loops.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Records Ctrl+C instead of exiting, so that the review stops at the next
+/// command after saving the session. The prompts read Ctrl+C as a key and
+/// quit by themselves, this catches it while the review is busy.
+pub fn handle_interrupts() -> anyhow::Result<()> {
+    ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst))?;
+
+    Ok(())
+}
+
+/// Whether the user pressed Ctrl+C since the review started.
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{rate_reviewed, undo_last_rating};
+    use crate::spaced_repetition::Evaluation;
+
+    fn command(task: &str) -> Command {
+        toml::from_str(&format!("task = \"{}\"\ncommands = [\"ls\"]", task)).unwrap()
+    }
+
+    fn tasks(commands: &VecDeque<Command>) -> Vec<&str> {
+        commands.iter().map(|command| command.task.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn a_resumed_review_skips_the_rated_commands() {
+        let _database = database::create_test_database().await;
+        database::save_commands(&[command("a"), command("b"), command("c")]).await.unwrap();
+
+        let (mut session, mut commands) = start_review().await.unwrap();
+        save_review(&session, &commands).await.unwrap();
+        assert_eq!(tasks(&commands), vec!["a", "b", "c"]);
+
+        let passed = commands.pop_front().unwrap();
+        rate_reviewed(&mut session, &mut commands, passed, &Evaluation::CorrectButHard, Duration::ZERO, 0, &[]).await.unwrap();
+        let failed = commands.pop_front().unwrap();
+        rate_reviewed(&mut session, &mut commands, failed, &Evaluation::Blackout, Duration::ZERO, 0, &[]).await.unwrap();
+
+        let (resumed, commands) = start_review().await.unwrap();
+        assert_eq!(tasks(&commands), vec!["c", "b"]);
+        assert_eq!(resumed.reviewed, 2);
+        assert_eq!(resumed.original_size, 3);
+        assert_eq!(resumed.requeued, vec![commands[1].id.unwrap()]);
+    }
+
+    #[tokio::test]
+    async fn undoing_a_failed_rating_undoes_its_requeue() {
+        let _database = database::create_test_database().await;
+        database::save_commands(&[command("a"), command("b")]).await.unwrap();
+
+        let (mut session, mut commands) = start_review().await.unwrap();
+        let failed = commands.pop_front().unwrap();
+        rate_reviewed(&mut session, &mut commands, failed, &Evaluation::Blackout, Duration::ZERO, 0, &[]).await.unwrap();
+        assert_eq!(tasks(&commands), vec!["b", "a"]);
+
+        let current = commands.pop_front().unwrap();
+        assert!(undo_last_rating(&mut session, &mut commands, current).await.unwrap());
+
+        assert_eq!(tasks(&commands), vec!["a", "b"]);
+        assert!(session.requeued.is_empty());
+        assert_eq!(session.reviewed, 0);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::utilities::get_current_date;
 use rand::Rng;
+use std::time::Duration;
 
 use super::{
     SRData,
@@ -40,6 +41,39 @@ impl Evaluation {
             Evaluation::Perfect => 5,
         }
     }
+
+    /// Lowers a correct evaluation according to the number of hints used:
+    /// one hint caps it at CorrectWithHesitation, more at CorrectButHard.
+    pub fn capped(self, hints: usize) -> Evaluation {
+        let cap = match hints {
+            0 => Evaluation::Perfect,
+            1 => Evaluation::CorrectWithHesitation,
+            _ => Evaluation::CorrectButHard,
+        };
+
+        if self.get_num() >= 3 && self.get_num() > cap.get_num() { cap } else { self }
+    }
+
+    /// The evaluation of a scenario with `correct` of its `steps` answered
+    /// correctly: Perfect when all of them are, and one grade lower for
+    /// every fifth of the steps failed.
+    pub fn from_steps(correct: usize, steps: usize) -> Evaluation {
+        if steps == 0 {
+            return Evaluation::Perfect;
+        }
+
+        ((correct.min(steps) * 5 / steps) as i64).into()
+    }
+
+    /// The three evaluations offered to the user after answering a command.
+    pub fn choices(is_correct: bool) -> [Evaluation; 3] {
+        if is_correct {
+            [Evaluation::CorrectButHard, Evaluation::CorrectWithHesitation, Evaluation::Perfect]
+        }
+        else {
+            [Evaluation::Blackout, Evaluation::IncorrectButRemembered, Evaluation::IncorrectWithEasyRecall]
+        }
+    }
 }
 
 pub fn compute_sr_data(sr_data: &SRData, evaluation: &Evaluation, add_noise_to_interval: bool) -> SRData {
@@ -98,13 +132,67 @@ fn generate_noise(input: i64) -> i64 {
 
     noise.ceil() as i64
 }
+/// Samples needed before the response times of a command are trusted.
+const MIN_COMMAND_SAMPLES: usize = 3;
+/// Samples needed before the typing speed of the user is trusted.
+const MIN_USER_SAMPLES: usize = 5;
+
+/// The time a correct answer of `characters` characters usually takes,
+/// from the previous response times of the same command or, if there are
+/// not enough, from the typing speed of the user in all the commands.
+pub fn response_baseline(command_times: &[Duration], user_times: &[(Duration, usize)], characters: usize) -> Option<Duration> {
+    if command_times.len() >= MIN_COMMAND_SAMPLES {
+        let seconds = median(command_times.iter().map(|time| time.as_secs_f64()).collect())?;
+        return Some(Duration::from_secs_f64(seconds));
+    }
+
+    if user_times.len() >= MIN_USER_SAMPLES {
+        let seconds_per_character = median(user_times.iter()
+            .map(|(time, characters)| time.as_secs_f64() / (*characters).max(1) as f64)
+            .collect())?;
+        return Some(Duration::from_secs_f64(seconds_per_character * characters.max(1) as f64));
+    }
+
+    None
+}
+
+/// Suggests how well a correct answer was remembered by comparing the time
+/// it took with the usual one: fast answers are perfect, slow ones hard.
+pub fn suggest_evaluation(response_time: Duration, baseline: Duration) -> Evaluation {
+    let ratio = response_time.as_secs_f64() / baseline.as_secs_f64().max(0.001);
+
+    if ratio <= 0.8 {
+        Evaluation::Perfect
+    }
+    else if ratio <= 1.5 {
+        Evaluation::CorrectWithHesitation
+    }
+    else {
+        Evaluation::CorrectButHard
+    }
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
 
+    values.sort_by(|a, b| a.total_cmp(b));
+    let middle = values.len() / 2;
+
+    if values.len().is_multiple_of(2) {
+        Some((values[middle - 1] + values[middle]) / 2.0)
+    }
+    else {
+        Some(values[middle])
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use chrono::Duration;
 
-    use crate::spaced_repetition::{compute_sr_data, Evaluation};
+    use crate::spaced_repetition::{compute_sr_data, response_baseline, suggest_evaluation, Evaluation};
     use crate::{SRData, Mode};
     use crate::utilities::get_current_date;
 
@@ -120,4 +208,39 @@ mod tests {
             }, &Evaluation::CorrectButHard, false)
         )
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn response_baseline_prefers_the_command_times() {
+        let seconds = std::time::Duration::from_secs;
+        let user_times = vec![(seconds(10), 10); 5];
+
+        assert_eq!(response_baseline(&[seconds(4), seconds(2), seconds(9)], &user_times, 20), Some(seconds(4)));
+        assert_eq!(response_baseline(&[seconds(4)], &user_times, 20), Some(seconds(20)));
+        assert_eq!(response_baseline(&[seconds(4)], &user_times[..2], 20), None);
+    }
+
+    #[test]
+    fn hints_cap_correct_evaluations() {
+        assert_eq!(Evaluation::Perfect.capped(0).get_num(), 5);
+        assert_eq!(Evaluation::Perfect.capped(1).get_num(), 4);
+        assert_eq!(Evaluation::CorrectWithHesitation.capped(3).get_num(), 3);
+        assert_eq!(Evaluation::Blackout.capped(3).get_num(), 0);
+    }
+
+    #[test]
+    fn fast_answers_are_suggested_as_perfect() {
+        let seconds = std::time::Duration::from_secs;
+
+        assert_eq!(suggest_evaluation(seconds(3), seconds(5)).get_num(), Evaluation::Perfect.get_num());
+        assert_eq!(suggest_evaluation(seconds(6), seconds(5)).get_num(), Evaluation::CorrectWithHesitation.get_num());
+        assert_eq!(suggest_evaluation(seconds(20), seconds(5)).get_num(), Evaluation::CorrectButHard.get_num());
+    }
+
+    #[test]
+    fn scenarios_are_evaluated_by_their_correct_steps() {
+        assert_eq!(Evaluation::from_steps(3, 3).get_num(), 5);
+        assert_eq!(Evaluation::from_steps(2, 3).get_num(), 3);
+        assert_eq!(Evaluation::from_steps(1, 2).get_num(), 2);
+        assert_eq!(Evaluation::from_steps(0, 3).get_num(), 0);
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::{BTreeMap, HashSet};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use colored::*;
+use comfy_table::{Cell, Color};
+use crate::api::new_table;
+use crate::tags::is_within;
+use crate::utilities::get_current_date;
+use super::database;
+use super::session::ReviewResult;
+use super::{Command, Mode};
+
+/// Commands with an interval of at least this many days are mature.
+const MATURE_INTERVAL: i64 = 21;
+const FORECAST_DAYS: i64 = 30;
+const HEATMAP_WEEKS: i64 = 26;
+const CRAM_DAYS: i64 = 30;
+
+/// Shows the state of the saved commands and how the reviews went,
+/// only for the commands with `tag` if given.
+pub async fn show_stats(tag: Option<&str>) -> anyhow::Result<()> {
+    let commands = match tag {
+        Some(tag) => database::find_commands_with_tag(tag).await?,
+        None => database::find_all_commands().await?
+    };
+    let today = get_current_date();
+
+    let mut results = database::find_review_log(today - Duration::days(365)).await?;
+    if let Some(tag) = tag {
+        results.retain(|result| result.tags.iter().any(|result_tag| is_within(result_tag, tag)));
+    }
+
+    if commands.is_empty() {
+        println!("RECLI: There are no commands to show stats for.");
+        return Ok(());
+    }
+
+    let mut table = new_table(vec!["Commands", ""]);
+    let count_mode = |mode: Mode| commands.iter().filter(|command| command.sr_data.mode == mode).count();
+    table.add_row(vec![Cell::new("New"), Cell::new(count_mode(Mode::New))]);
+    table.add_row(vec![Cell::new("Learning"), Cell::new(count_mode(Mode::Learning))]);
+    table.add_row(vec![Cell::new("Failed"), Cell::new(count_mode(Mode::Failed))]);
+
+    let (mature, young) = count_mature(commands.iter());
+    table.add_row(vec![Cell::new(format!("Mature (interval >= {} days)", MATURE_INTERVAL)), Cell::new(mature)]);
+    table.add_row(vec![Cell::new("Young"), Cell::new(young)]);
+
+    let average_ease = commands.iter().map(|command| command.sr_data.e_factor).sum::<f64>() / commands.len() as f64;
+    table.add_row(vec![Cell::new("Average ease"), Cell::new(format!("{:.0}%", average_ease * 100.0))]);
+
+    for days in [7, 30, 365] {
+        let retention = match retention(&results, today - Duration::days(days)) {
+            Some(retention) => format!("{:.0}%", retention * 100.0),
+            None => "-".to_string(),
+        };
+        table.add_row(vec![Cell::new(format!("Retention, last {} days", days)), Cell::new(retention)]);
+    }
+    println!("{table}");
+
+    let mut table = new_table(vec!["Due", "Commands", ""]);
+    let suspended: HashSet<i64> = database::find_suspended().await?.into_iter().collect();
+    let forecast = forecast(commands.iter(), &suspended, today, FORECAST_DAYS);
+    let max = forecast.iter().copied().max().unwrap_or(0).max(1);
+    for (day, count) in forecast.iter().enumerate() {
+        let label = match day {
+            0 => "Today".to_string(),
+            1 => "Tomorrow".to_string(),
+            _ => (today + Duration::days(day as i64)).format("%a %d %b").to_string(),
+        };
+        table.add_row(vec![
+            Cell::new(label),
+            Cell::new(count),
+            Cell::new("■".repeat(count * 30 / max)).fg(Color::Cyan),
+        ]);
+    }
+    println!("{table}");
+
+    println!("{}", "Reviews".bold());
+    print!("{}", heatmap(&results, today.date_naive(), HEATMAP_WEEKS));
+
+    let mut cram = database::find_cram_log(today - Duration::days(CRAM_DAYS)).await?;
+    if let Some(tag) = tag {
+        cram.retain(|result| result.tags.iter().any(|result_tag| is_within(result_tag, tag)));
+    }
+    if !cram.is_empty() {
+        println!("{}", cram_table(&cram));
+    }
+
+    Ok(())
+}
+
+/// How the cramming went: the answers, their accuracy and the commands
+/// missed the most.
+fn cram_table(results: &[ReviewResult]) -> comfy_table::Table {
+    let mut table = new_table(vec![&format!("Cram, last {} days", CRAM_DAYS), ""]);
+    let correct = results.iter().filter(|result| result.is_correct).count();
+    table.add_row(vec![Cell::new("Answers"), Cell::new(results.len())]);
+    table.add_row(vec![Cell::new("Accuracy"), Cell::new(format!("{:.0}%", correct as f64 / results.len() as f64 * 100.0))]);
+
+    let response_times: Vec<std::time::Duration> = results.iter().filter_map(|result| result.response_time).collect();
+    if !response_times.is_empty() {
+        let average = response_times.iter().sum::<std::time::Duration>() / response_times.len() as u32;
+        table.add_row(vec![Cell::new("Average response time"), Cell::new(format!("{:.1}s", average.as_secs_f64()))]);
+    }
+
+    let mut missed: BTreeMap<&str, usize> = BTreeMap::new();
+    for result in results.iter().filter(|result| !result.is_correct) {
+        *missed.entry(result.task.as_str()).or_default() += 1;
+    }
+    let mut missed: Vec<(&str, usize)> = missed.into_iter().collect();
+    missed.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    if !missed.is_empty() {
+        let most_missed: Vec<String> = missed.iter().take(5).map(|(task, count)| format!("{} ({})", task, count)).collect();
+        table.add_row(vec![Cell::new("Most missed"), Cell::new(most_missed.join("\n")).fg(Color::Red)]);
+    }
+
+    table
+}
+
+/// Counts the mature and the young learned commands.
+fn count_mature<'a>(commands: impl Iterator<Item = &'a Command>) -> (usize, usize) {
+    commands
+        .filter(|command| command.sr_data.mode == Mode::Learning)
+        .fold((0, 0), |(mature, young), command| {
+            if command.sr_data.interval >= MATURE_INTERVAL { (mature + 1, young) } else { (mature, young + 1) }
+        })
+}
+
+/// The share of reviews of learned commands since `since` that were
+/// answered correctly, `None` if there were none.
+fn retention(results: &[ReviewResult], since: DateTime<Utc>) -> Option<f64> {
+    let reviews: Vec<&ReviewResult> = results.iter()
+        .filter(|result| result.reviewed >= since && result.mode_before == Mode::Learning)
+        .collect();
+
+    if reviews.is_empty() {
+        return None;
+    }
+
+    let correct = reviews.iter().filter(|result| result.is_correct).count();
+    Some(correct as f64 / reviews.len() as f64)
+}
+
+/// The number of commands that will be due on each of the next `days` days,
+/// assuming they are reviewed on their due date. Overdue, new and failed
+/// commands are due today, and `suspended` ones never, as in the reviews.
+fn forecast<'a>(commands: impl Iterator<Item = &'a Command>, suspended: &HashSet<i64>, today: DateTime<Utc>, days: i64) -> Vec<usize> {
+    let mut forecast = vec![0; days as usize];
+
+    for command in commands.filter(|command| !command.id.is_some_and(|id| suspended.contains(&id))) {
+        let day = match command.sr_data.due_date() {
+            Some(due_date) => (due_date - today).num_days().max(0),
+            None => 0,
+        };
+
+        if day < days {
+            forecast[day as usize] += 1;
+        }
+    }
+
+    forecast
+}
+
+/// A calendar of the last `weeks` weeks, one column per week and one row
+/// per weekday, shaded by the number of reviews made each day.
+fn heatmap(results: &[ReviewResult], today: NaiveDate, weeks: i64) -> String {
+    let mut reviews: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    for result in results {
+        *reviews.entry(result.reviewed.date_naive()).or_default() += 1;
+    }
+    let max = reviews.values().copied().max().unwrap_or(0);
+
+    let start = today
+        - Duration::days(today.weekday().num_days_from_monday() as i64)
+        - Duration::weeks(weeks - 1);
+
+    let mut months = String::from("    ");
+    let mut last_month = None;
+    for week in 0..weeks {
+        let monday = start + Duration::weeks(week);
+        let column = 4 + 2 * week as usize;
+
+        if last_month != Some(monday.month()) && months.chars().count() <= column {
+            months.push_str(&format!("{:width$}{}", "", monday.format("%b"), width = column - months.chars().count()));
+            last_month = Some(monday.month());
+        }
+    }
+
+    let mut calendar = format!("{}\n", months.trim_end());
+    for weekday in 0..7 {
+        let label = ["Mon", "", "Wed", "", "Fri", "", "Sun"][weekday];
+        calendar.push_str(&format!("{:<4}", label));
+
+        for week in 0..weeks {
+            let date = start + Duration::weeks(week) + Duration::days(weekday as i64);
+            if date > today {
+                break;
+            }
+
+            let count = reviews.get(&date).copied().unwrap_or(0);
+            calendar.push_str(&shade(count, max));
+            calendar.push(' ');
+        }
+        calendar.push('\n');
+    }
+    calendar.push_str(&format!("    less {} {} {} {} {} more\n", shade(0, 4), shade(1, 4), shade(2, 4), shade(3, 4), shade(4, 4)));
+
+    calendar
+}
+
+fn shade(count: usize, max: usize) -> String {
+    if count == 0 {
+        return "·".dimmed().to_string();
+    }
+
+    let level = (count * 4).div_ceil(max.max(1));
+    ["░", "▒", "▓", "█"][level.clamp(1, 4) - 1].green().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CardKind, SRData};
+
+    fn command(mode: Mode, last_review: Option<DateTime<Utc>>, interval: i64) -> Command {
+        Command {
+            id: None,
+            task: String::new(),
+            clues: None,
+            context: None,
+            prompt: None,
+            commands: vec![],
+            response: None,
+            extra: None,
+            tags: None,
+            fixtures: None,
+            kind: CardKind::Recall,
+            flag: None,
+            cloze: None,
+            group: None,
+            steps: vec![],
+            sr_data: SRData { mode, last_review, interval, ..SRData::default() },
+        }
+    }
+
+    #[test]
+    fn forecast_projects_the_next_review() {
+        let today = get_current_date();
+        let commands = [
+            command(Mode::New, None, 1),
+            command(Mode::Learning, Some(today - Duration::days(10)), 6),
+            command(Mode::Learning, Some(today - Duration::days(1)), 3),
+            command(Mode::Learning, Some(today), 40),
+        ];
+
+        let forecast = forecast(commands.iter(), &HashSet::new(), today, 5);
+
+        assert_eq!(forecast, vec![2, 0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn suspended_commands_are_not_forecast() {
+        let today = get_current_date();
+        let mut commands = [command(Mode::New, None, 1), command(Mode::Failed, Some(today), 1)];
+        commands[0].id = Some(1);
+        commands[1].id = Some(2);
+
+        let forecast = forecast(commands.iter(), &HashSet::from([2]), today, 2);
+
+        assert_eq!(forecast, vec![1, 0]);
+    }
+
+    #[test]
+    fn retention_only_counts_learned_commands() {
+        let result = |mode_before: Mode, is_correct: bool| ReviewResult {
+            command_id: 1,
+            reviewed: Utc::now(),
+            mode_before,
+            task: String::new(),
+            tags: vec![],
+            is_correct,
+            response_time: None,
+            hints: 0,
+            e_factor_before: None,
+            e_factor_after: None,
+            lapsed: false,
+            failed_steps: vec![],
+        };
+        let results = [
+            result(Mode::Learning, true),
+            result(Mode::Learning, false),
+            result(Mode::Learning, true),
+            result(Mode::Learning, true),
+            result(Mode::New, false),
+        ];
+
+        assert_eq!(retention(&results, Utc::now() - Duration::days(7)), Some(0.75));
+        assert_eq!(retention(&[], Utc::now() - Duration::days(7)), None);
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::{BTreeMap, HashSet};
+use anyhow::bail;
+use comfy_table::{Cell, Color};
+use crate::api::new_table;
+use super::database;
+
+/// Separates a tag from its parent, as in `k8s::kubectl::logs`.
+pub const SEPARATOR: &str = "::";
+
+#[derive(clap::Subcommand, Debug)]
+pub enum TagAction {
+    /// Add a tag to the selected commands
+    Add {
+        #[arg(value_name = "TAG")]
+        tag: String,
+        #[command(flatten)]
+        selection: Selection,
+    },
+    /// Remove a tag and its children from the selected commands, or from every command with --all
+    Remove {
+        #[arg(value_name = "TAG")]
+        tag: String,
+        #[command(flatten)]
+        selection: Selection,
+        /// Remove it from every command
+        #[arg(long, conflicts_with_all = ["ids", "search", "tagged"])]
+        all: bool,
+    },
+    /// Rename a tag and its children
+    Rename {
+        #[arg(value_name = "TAG")]
+        old: String,
+        #[arg(value_name = "NEW_TAG")]
+        new: String,
+    },
+    /// List the tags with their number of commands and of commands due today
+    List,
+}
+
+/// The commands a tag operation applies to.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct Selection {
+    /// A command, by its id in 'recli show'
+    #[arg(long = "id", value_name = "ID")]
+    pub ids: Vec<i64>,
+    /// The commands found by a search
+    #[arg(long, value_name = "QUERY")]
+    pub search: Option<String>,
+    /// The commands with a tag, or one of its children
+    #[arg(long, value_name = "TAG")]
+    pub tagged: Option<String>,
+}
+
+impl Selection {
+    /// The ids of the selected commands, `None` if nothing was selected.
+    async fn find_ids(&self) -> anyhow::Result<Option<Vec<i64>>> {
+        if self.ids.is_empty() && self.search.is_none() && self.tagged.is_none() {
+            return Ok(None);
+        }
+
+        let mut ids: Vec<i64> = self.ids.clone();
+        if let Some(query) = &self.search {
+            ids.extend(database::search_commands(query).await?.iter().filter_map(|(command, _)| command.id));
+        }
+        if let Some(tag) = &self.tagged {
+            ids.extend(database::find_commands_with_tag(tag).await?.iter().filter_map(|command| command.id));
+        }
+        ids.sort();
+        ids.dedup();
+
+        Ok(Some(ids))
+    }
+}
+
+/// Whether `tag` is `parent` or one of its children.
+pub fn is_within(tag: &str, parent: &str) -> bool {
+    tag == parent || tag.strip_prefix(parent).is_some_and(|rest| rest.starts_with(SEPARATOR))
+}
+
+/// `tag` with `old` or its parent replaced by `new`, `None` if it is not within `old`.
+pub fn renamed(tag: &str, old: &str, new: &str) -> Option<String> {
+    is_within(tag, old).then(|| format!("{}{}", new, &tag[old.len()..]))
+}
+
+/// The tag and its parents, the outermost first.
+fn with_parents(tag: &str) -> Vec<String> {
+    let parts: Vec<&str> = tag.split(SEPARATOR).collect();
+    (1..=parts.len()).map(|count| parts[..count].join(SEPARATOR)).collect()
+}
+
+pub async fn manage(action: &TagAction) -> anyhow::Result<()> {
+    match action {
+        TagAction::Add { tag, selection } => {
+            let Some(ids) = selection.find_ids().await? else {
+                bail!("Select the commands to tag with --id, --search or --tagged");
+            };
+            let added = database::add_tag(&ids, tag).await?;
+            println!("RECLI: Tagged {} of {} commands with {}.", added, ids.len(), tag);
+        },
+        TagAction::Remove { tag, selection, all } => {
+            let ids = selection.find_ids().await?;
+            if ids.is_none() && !all {
+                bail!("Select the commands to untag with --id, --search or --tagged, or use --all");
+            }
+            let removed = database::remove_tag(ids.as_deref(), tag).await?;
+            println!("RECLI: Removed {} from {} commands.", tag, removed);
+        },
+        TagAction::Rename { old, new } => {
+            let renamed = database::rename_tag(old, new).await?;
+            println!("RECLI: Renamed {} to {} in {} commands.", old, new, renamed);
+        },
+        TagAction::List => show_tags().await?,
+    }
+
+    Ok(())
+}
+
+/// Shows every tag, and the parents of nested tags, with how many commands
+/// they and their children have and how many of those are due today.
+async fn show_tags() -> anyhow::Result<()> {
+    let commands = database::find_all_commands().await?;
+    let suspended: HashSet<i64> = database::find_suspended().await?.into_iter().collect();
+    // Keyed by the parts of the tags, so children are listed under their parent
+    let mut counts: BTreeMap<Vec<String>, (HashSet<i64>, HashSet<i64>)> = BTreeMap::new();
+
+    for command in &commands {
+        let id = command.id.unwrap_or(0);
+        let is_due = command.is_pending() && !suspended.contains(&id);
+
+        for tag in command.tags.iter().flatten().flat_map(|tag| with_parents(tag)) {
+            let parts = tag.split(SEPARATOR).map(|part| part.to_string()).collect();
+            let (tagged, due) = counts.entry(parts).or_default();
+            tagged.insert(id);
+            if is_due { due.insert(id); }
+        }
+    }
+
+    if counts.is_empty() {
+        println!("RECLI: There are no tags.");
+        return Ok(());
+    }
+
+    let mut table = new_table(vec!["Tag", "Commands", "Due"]);
+    for (parts, (tagged, due)) in counts {
+        let name = parts.last().map(|part| part.as_str()).unwrap_or_default();
+        table.add_row(vec![
+            Cell::new(format!("{}{}", "  ".repeat(parts.len() - 1), name)).fg(Color::Cyan),
+            Cell::new(tagged.len()),
+            Cell::new(due.len()),
+        ]);
+    }
+    println!("{table}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parent_tags_include_their_children() {
+        assert!(is_within("k8s::kubectl::logs", "k8s"));
+        assert!(is_within("k8s", "k8s"));
+        assert!(!is_within("k8sx", "k8s"));
+        assert_eq!(renamed("k8s::kubectl", "k8s", "kube"), Some("kube::kubectl".to_string()));
+        assert_eq!(with_parents("k8s::kubectl::logs"), vec!["k8s", "k8s::kubectl", "k8s::kubectl::logs"]);
+    }
+}
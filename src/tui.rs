@@ -0,0 +1,760 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Position, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span, Text};
+use ratatui::widgets::{Block, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use crate::api::{rate_reviewed, show_review_report, undo_last_rating};
+use crate::cards;
+use crate::grading::{diff, grade, DiffPart, Grade};
+use crate::hints::hint_ladder;
+use crate::session::{handle_interrupts, is_interrupted, save_review, ReviewSession};
+use crate::spaced_repetition::{Evaluation, compute_sr_data, response_baseline, suggest_evaluation};
+use super::database;
+use super::{CardKind, Command, Mode, Step};
+
+/// The answer line, with a cursor and the answers already given in this session.
+#[derive(Default)]
+struct Input {
+    text: String,
+    cursor: usize,
+    history: Vec<String>,
+    history_index: Option<usize>,
+}
+
+impl Input {
+    fn byte_index(&self) -> usize {
+        self.text.char_indices().nth(self.cursor).map(|(i, _)| i).unwrap_or(self.text.len())
+    }
+
+    fn insert(&mut self, c: char) {
+        let index = self.byte_index();
+        self.text.insert(index, c);
+        self.cursor += 1;
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor == 0 { return; }
+        self.cursor -= 1;
+        let index = self.byte_index();
+        self.text.remove(index);
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.text.chars().count() {
+            let index = self.byte_index();
+            self.text.remove(index);
+        }
+    }
+
+    fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.text.chars().count());
+    }
+
+    fn set_text(&mut self, text: String) {
+        self.cursor = text.chars().count();
+        self.text = text;
+    }
+
+    fn previous(&mut self) {
+        if self.history.is_empty() { return; }
+
+        let index = match self.history_index {
+            Some(index) => index.saturating_sub(1),
+            None => self.history.len() - 1,
+        };
+        self.history_index = Some(index);
+        self.set_text(self.history[index].clone());
+    }
+
+    fn next(&mut self) {
+        let Some(index) = self.history_index else { return };
+
+        if index + 1 < self.history.len() {
+            self.history_index = Some(index + 1);
+            self.set_text(self.history[index + 1].clone());
+        }
+        else {
+            self.history_index = None;
+            self.set_text(String::new());
+        }
+    }
+
+    /// Clears the line and returns what was typed, saving it in the history.
+    fn submit(&mut self) -> String {
+        let text = std::mem::take(&mut self.text);
+        self.cursor = 0;
+        self.history_index = None;
+        if !text.trim().is_empty() {
+            self.history.push(text.clone());
+        }
+        text
+    }
+}
+
+enum Stage {
+    /// `retried` is set after a near miss, when the user is asked to try again,
+    /// and `steps` has the answers to the steps of a scenario given so far
+    Answering { retried: bool, steps: Vec<(String, Grade)> },
+    /// The user grades their explanation of an explain card
+    Assessing { answer: String },
+    Answered { answer: String, grade: Grade, retried: bool, steps: Vec<(String, Grade)> },
+}
+
+impl Stage {
+    /// The tasks of the steps of `command`, a scenario, answered wrong.
+    fn failed_steps(&self, command: &Command) -> Vec<String> {
+        let (Stage::Answering { steps, .. } | Stage::Answered { steps, .. }) = self else { return vec![] };
+
+        command.steps.iter().zip(steps)
+            .filter(|(_, (_, grade))| !grade.is_correct())
+            .map(|(step, _)| step.task.clone())
+            .collect()
+    }
+}
+
+enum Action {
+    None,
+    Rate(Evaluation),
+    Skip,
+    Suspend,
+    Undo,
+    Quit,
+}
+
+/// The card being reviewed and how far it was answered. The keys pressed
+/// change it apart from the drawing, which only reads it.
+struct Card {
+    command: Command,
+    /// The choices of multiple choice and reverse cards
+    options: Vec<String>,
+    /// The hints of typed answers, of which `hints` were shown
+    ladder: Vec<String>,
+    hints: usize,
+    stage: Stage,
+    started: Instant,
+    response_time: Duration,
+    /// The past response times to the command and of the user, see `response_baseline`
+    response_times: (Vec<Duration>, Vec<(Duration, usize)>),
+    /// The rating suggested from the response time, and the usual one
+    suggestion: Option<(Evaluation, Duration)>,
+}
+
+impl Card {
+    fn new(command: Command, options: Vec<String>, response_times: (Vec<Duration>, Vec<(Duration, usize)>)) -> Self {
+        // Hints are only given for typed answers
+        let ladder = if command.kind.is_typed() { hint_ladder(&command) } else { vec![] };
+
+        Self {
+            command,
+            options,
+            ladder,
+            hints: 0,
+            stage: Stage::Answering { retried: false, steps: vec![] },
+            started: Instant::now(),
+            response_time: Duration::ZERO,
+            response_times,
+            suggestion: None,
+        }
+    }
+
+    /// How much the rating is capped: retrying after a typo counts as two hints.
+    fn penalty(&self) -> usize {
+        match self.stage {
+            Stage::Answered { retried: true, .. } => self.hints + 2,
+            _ => self.hints,
+        }
+    }
+
+    /// Shows the result of `answer` and, if it was typed correctly, suggests
+    /// a rating from the time taken.
+    fn answered(&mut self, answer: String, grade: Grade, retried: bool, steps: Vec<(String, Grade)>) {
+        // The time of explain cards stops before they are assessed
+        if let Stage::Answering { .. } = self.stage {
+            self.response_time = self.started.elapsed();
+        }
+
+        let characters = answer.trim().chars().count();
+        let is_correct = grade.is_correct();
+        self.stage = Stage::Answered { answer, grade, retried, steps };
+
+        if is_correct && self.command.kind.is_typed() {
+            let (command_times, user_times) = &self.response_times;
+            self.suggestion = response_baseline(command_times, user_times, characters)
+                .map(|baseline| (suggest_evaluation(self.response_time, baseline).capped(self.penalty()), baseline));
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent, input: &mut Input) -> Action {
+        let command = &self.command;
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match &self.stage {
+            Stage::Answering { retried, steps } => match key.code {
+                KeyCode::Enter if command.kind == CardKind::Scenario => {
+                    let mut steps = steps.clone();
+                    let answer = input.submit();
+                    let grade = grade(&answer, &command.steps[steps.len()].commands);
+                    steps.push((answer, grade));
+
+                    if steps.len() < command.steps.len() {
+                        self.stage = Stage::Answering { retried: false, steps };
+                    }
+                    else {
+                        let answer = steps.iter().map(|(answer, _)| answer.as_str()).collect::<Vec<&str>>().join("\n");
+                        let grade = match steps.iter().find(|(_, grade)| !grade.is_correct()) {
+                            Some(_) => Grade::Wrong { expected: String::new() },
+                            None => Grade::Exact,
+                        };
+                        self.answered(answer, grade, false, steps);
+                    }
+                    Action::None
+                },
+                KeyCode::Enter => {
+                    let retried = *retried;
+                    let answer = input.submit();
+                    let grade = match command.kind {
+                        CardKind::Recall | CardKind::Cloze => grade(&answer, &command.commands),
+                        CardKind::MultipleChoice | CardKind::Reverse => cards::grade_choice(command, &self.options, &answer),
+                        CardKind::Explain | CardKind::Scenario => Grade::Wrong { expected: cards::expected_answer(command) },
+                    };
+
+                    if command.kind == CardKind::Explain {
+                        self.response_time = self.started.elapsed();
+                        self.stage = Stage::Assessing { answer };
+                    }
+                    else if let (Grade::NearMiss { .. }, false, true) = (&grade, retried, command.kind.is_typed()) {
+                        input.set_text(answer);
+                        self.stage = Stage::Answering { retried: true, steps: vec![] };
+                    }
+                    else {
+                        self.answered(answer, grade, retried, vec![]);
+                    }
+                    Action::None
+                },
+                KeyCode::Esc => Action::Quit,
+                KeyCode::Char('c') if ctrl => Action::Quit,
+                KeyCode::Char('s') if ctrl => Action::Skip,
+                KeyCode::Char('x') if ctrl => Action::Suspend,
+                KeyCode::Char('t') if ctrl => { self.hints = (self.hints + 1).min(self.ladder.len()); Action::None },
+                KeyCode::Char('a') if ctrl => { input.cursor = 0; Action::None },
+                KeyCode::Char('e') if ctrl => { input.cursor = input.text.chars().count(); Action::None },
+                KeyCode::Char('u') if ctrl => { input.set_text(String::new()); Action::None },
+                // Other shortcuts, such as Ctrl+Z, are not typed
+                KeyCode::Char(_) if ctrl => Action::None,
+                KeyCode::Char(c) => { input.insert(c); Action::None },
+                KeyCode::Backspace => { input.backspace(); Action::None },
+                KeyCode::Delete => { input.delete(); Action::None },
+                KeyCode::Left => { input.left(); Action::None },
+                KeyCode::Right => { input.right(); Action::None },
+                KeyCode::Home => { input.cursor = 0; Action::None },
+                KeyCode::End => { input.cursor = input.text.chars().count(); Action::None },
+                KeyCode::Up => { input.previous(); Action::None },
+                KeyCode::Down => { input.next(); Action::None },
+                _ => Action::None,
+            },
+            Stage::Assessing { answer } => match key.code {
+                KeyCode::Char(c @ ('y' | 'n')) => {
+                    let grade = if c == 'y' { Grade::Exact } else { Grade::Wrong { expected: cards::expected_answer(command) } };
+                    let answer = answer.clone();
+                    self.answered(answer, grade, false, vec![]);
+                    Action::None
+                },
+                KeyCode::Char('q') | KeyCode::Esc => Action::Quit,
+                KeyCode::Char('c') if ctrl => Action::Quit,
+                _ => Action::None,
+            },
+            Stage::Answered { grade, steps, .. } => match (key.code, &command.sr_data.mode) {
+                // Scenarios are evaluated by how many of their steps were correct
+                (KeyCode::Enter, _) if command.kind == CardKind::Scenario => {
+                    let correct = steps.iter().filter(|(_, grade)| grade.is_correct()).count();
+                    Action::Rate(Evaluation::from_steps(correct, command.steps.len()))
+                },
+                (KeyCode::Char(c @ '1'..='3'), Mode::Learning) if command.kind != CardKind::Scenario => {
+                    let [hard, good, perfect] = Evaluation::choices(grade.is_correct());
+                    match c {
+                        '1' => Action::Rate(hard),
+                        '2' => Action::Rate(good),
+                        _ => Action::Rate(perfect),
+                    }
+                },
+                (KeyCode::Enter, Mode::Learning) if matches!(grade, Grade::NearMiss { .. }) => {
+                    Action::Rate(Evaluation::IncorrectWithEasyRecall)
+                },
+                (KeyCode::Enter, Mode::Learning) => match &self.suggestion {
+                    Some((evaluation, _)) => Action::Rate(evaluation.get_num().into()),
+                    None => Action::None,
+                },
+                (KeyCode::Enter, Mode::New | Mode::Failed) => {
+                    // New and failed commands are evaluated automatically, as in `api::review`
+                    Action::Rate(match grade {
+                        Grade::Exact => Evaluation::CorrectButHard,
+                        Grade::NearMiss { .. } => Evaluation::IncorrectWithEasyRecall,
+                        Grade::Wrong { .. } => Evaluation::Blackout,
+                    })
+                },
+                (KeyCode::Char('s'), _) => Action::Skip,
+                (KeyCode::Char('x'), _) => Action::Suspend,
+                (KeyCode::Char('u'), _) => Action::Undo,
+                (KeyCode::Char('q') | KeyCode::Esc, _) => Action::Quit,
+                (KeyCode::Char('c'), _) if ctrl => Action::Quit,
+                _ => Action::None,
+            },
+        }
+    }
+}
+
+/// Full-screen version of `api::review`.
+pub async fn review(session: &mut ReviewSession, commands: &mut VecDeque<Command>) -> anyhow::Result<()> {
+    save_review(session, commands).await?;
+    handle_interrupts()?;
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, session, commands).await;
+    ratatui::restore();
+
+    let suspended = result?;
+    if !suspended.is_empty() {
+        let ids: Vec<String> = suspended.iter().map(|id| id.to_string()).collect();
+        println!("RECLI: Suspended the commands {}, review them again with 'recli unsuspend --id ID'.", ids.join(", "));
+    }
+
+    if commands.is_empty() {
+        println!("RECLI: No more commands to review today.");
+    }
+    else {
+        session.print_summary(commands.len());
+    }
+
+    show_review_report(session).await
+}
+
+/// Reviews `commands` until there are none left or the user quits, returning
+/// the ids of the commands suspended.
+async fn run(terminal: &mut DefaultTerminal, session: &mut ReviewSession, commands: &mut VecDeque<Command>) -> anyhow::Result<Vec<i64>> {
+    let mut input = Input::default();
+    let mut suspended = vec![];
+
+    while !commands.is_empty() {
+        let progress = session.progress(commands);
+        let command = commands.pop_front().unwrap();
+        let options = cards::find_options(&command).await?;
+        let response_times = database::find_response_times(&command).await?;
+        let mut card = Card::new(command, options, response_times);
+
+        let action = loop {
+            terminal.draw(|frame| draw(frame, &card, &progress, &input))?;
+
+            // Ctrl+C is read as a key, the signal only comes from outside the terminal
+            if !event::poll(Duration::from_millis(250))? {
+                if is_interrupted() { break Action::Quit; }
+                continue;
+            }
+            let Event::Key(key) = event::read()? else { continue };
+            if key.kind != KeyEventKind::Press { continue; }
+
+            let action = card.handle_key(key, &mut input);
+            if !matches!(action, Action::None) {
+                break action;
+            }
+        };
+
+        let penalty = card.penalty();
+        let Card { command, stage, hints, response_time, .. } = card;
+        match action {
+            Action::Rate(evaluation) => {
+                if let Stage::Answered { answer, grade, .. } = &stage {
+                    if grade.is_correct() && command.kind.is_typed() {
+                        database::save_response_time(&command, answer.trim(), response_time).await?;
+                    }
+                }
+                let evaluation = evaluation.capped(penalty);
+                let failed_steps = stage.failed_steps(&command);
+                rate_reviewed(session, commands, command, &evaluation, response_time, hints, &failed_steps).await?;
+            },
+            Action::Skip => {
+                commands.push_back(command);
+                save_review(session, commands).await?;
+            },
+            Action::Suspend => {
+                database::suspend_command(&command).await?;
+                suspended.extend(command.id);
+                save_review(session, commands).await?;
+            },
+            Action::Undo => {
+                undo_last_rating(session, commands, command).await?;
+            },
+            Action::Quit => {
+                commands.push_front(command);
+                save_review(session, commands).await?;
+                break;
+            },
+            Action::None => unreachable!(),
+        }
+    }
+
+    Ok(suspended)
+}
+
+fn draw(frame: &mut Frame, card: &Card, progress: &str, input: &Input) {
+    let Card { command, options, stage, .. } = card;
+    let hints = &card.ladder[..card.hints];
+    let [task_area, notes_area, input_area, result_area, help_area] = Layout::vertical([
+        Constraint::Length(5),
+        Constraint::Fill(1),
+        Constraint::Length(3),
+        Constraint::Fill(2),
+        Constraint::Length(1),
+    ]).areas(frame.area());
+
+    let sr_data = &command.sr_data;
+    let (label, question) = cards::question(command);
+    let metadata = format!(
+        "{}  Ease: {}%  Interval: {} days  Reviews: {}  Tags: {}",
+        sr_data.mode,
+        (sr_data.e_factor * 100.0).floor(),
+        sr_data.interval,
+        sr_data.review_count,
+        command.tags.clone().unwrap_or_default().join(", "),
+    );
+    let mut lines: Vec<Line> = format!("{}: {}", label, question).lines()
+        .map(|line| Line::from(line.to_string()).cyan().bold())
+        .collect();
+    if let Some((number, step)) = current_step(command, stage) {
+        lines.push(Line::from(format!("Step {}/{}: {}", number, command.steps.len(), step.task)).cyan());
+    }
+    // The questions of cloze cards and scenarios take two lines, leaving no room for a blank one
+    if lines.len() == 1 {
+        lines.push(Line::from(""));
+    }
+    lines.push(Line::from(metadata).dark_gray());
+    let task = Text::from(lines);
+    frame.render_widget(
+        Paragraph::new(task)
+            .block(Block::bordered().title(format!(" RECLI {} ", progress).magenta().bold()))
+            .wrap(Wrap { trim: false }),
+        task_area,
+    );
+
+    let [hints_area, context_area] = Layout::horizontal([Constraint::Percentage(50); 2]).areas(notes_area);
+    if command.kind == CardKind::Scenario {
+        render_note(frame, " Steps ", Some(&steps_text(command, stage).join("\n")), Color::Reset, hints_area);
+    }
+    else if options.is_empty() {
+        render_note(frame, " Hints ", Some(&hints.join("\n")), Color::Cyan, hints_area);
+    }
+    else {
+        let options: Vec<String> = options.iter().enumerate().map(|(number, option)| format!("{}) {}", number + 1, option)).collect();
+        render_note(frame, " Options ", Some(&options.join("\n")), Color::Cyan, hints_area);
+    }
+    render_note(frame, " Context ", command.context.as_deref(), Color::Reset, context_area);
+
+    let prompt = command.prompt.as_deref().map(str::trim).unwrap_or(">");
+    let answer = match stage {
+        Stage::Answering { .. } => input.text.as_str(),
+        Stage::Assessing { answer } | Stage::Answered { answer, .. } => answer.as_str(),
+    };
+    frame.render_widget(
+        Paragraph::new(format!("{} {}", prompt, answer)).block(Block::bordered().title(match stage {
+            Stage::Answering { retried: true, .. } => " Almost! There is a typo, try again ".yellow(),
+            _ => " Answer ".into(),
+        })),
+        input_area,
+    );
+
+    if let Stage::Answering { .. } = stage {
+        let x = input_area.x + 1 + prompt.chars().count() as u16 + 1 + input.cursor as u16;
+        frame.set_cursor_position(Position::new(x.min(input_area.right().saturating_sub(2)), input_area.y + 1));
+    }
+
+    let help = match stage {
+        Stage::Answering { .. } => "Enter: answer  ↑/↓: history  Ctrl+T: hint  Ctrl+S: skip  Ctrl+X: suspend  Esc: quit",
+        Stage::Assessing { .. } => "y: correct  n: incorrect  q: quit",
+        Stage::Answered { .. } => match sr_data.mode {
+            Mode::Learning if command.kind != CardKind::Scenario => match (stage, &card.suggestion) {
+                (Stage::Answered { grade: Grade::NearMiss { .. }, .. }, _) | (_, Some(_)) => "1-3: rate  Enter: suggested  s: skip  x: suspend  u: undo  q: quit",
+                _ => "1-3: rate  s: skip  x: suspend  u: undo  q: quit",
+            },
+            _ => "Enter: continue  s: skip  x: suspend  u: undo  q: quit",
+        },
+    };
+
+    if let Stage::Assessing { .. } = stage {
+        let expected = Text::from(vec![
+            Line::from("Expected:"),
+            Line::from(cards::expected_answer(command)).green(),
+            Line::from(""),
+            Line::from("Did you explain it correctly? (y/n)"),
+        ]);
+        frame.render_widget(
+            Paragraph::new(expected)
+                .block(Block::bordered().title(" Result "))
+                .wrap(Wrap { trim: false }),
+            result_area,
+        );
+    }
+
+    if let Stage::Answered { answer, grade, retried, steps } = stage {
+        let result = match command.kind {
+            CardKind::Scenario => scenario_result(command, steps),
+            _ => result_text(card, answer, grade, *retried),
+        };
+        frame.render_widget(
+            Paragraph::new(result)
+                .block(Block::bordered().title(" Result "))
+                .wrap(Wrap { trim: false }),
+            result_area,
+        );
+    }
+
+    frame.render_widget(Paragraph::new(help).dark_gray(), help_area);
+}
+
+/// The number and the step of a scenario being answered.
+fn current_step<'a>(command: &'a Command, stage: &Stage) -> Option<(usize, &'a Step)> {
+    let Stage::Answering { steps, .. } = stage else { return None };
+    command.steps.get(steps.len()).map(|step| (steps.len() + 1, step))
+}
+
+/// The steps of a scenario answered so far, marked as correct or failed.
+fn steps_text(command: &Command, stage: &Stage) -> Vec<String> {
+    let (Stage::Answering { steps, .. } | Stage::Answered { steps, .. }) = stage else { return vec![] };
+
+    command.steps.iter().zip(steps).map(|(step, (_, grade))| match grade {
+        Grade::Exact => format!("✓ {}", step.task),
+        _ => format!("✗ {}", step.task),
+    }).collect()
+}
+
+fn render_note(frame: &mut Frame, title: &str, note: Option<&str>, color: Color, area: Rect) {
+    frame.render_widget(
+        Paragraph::new(note.unwrap_or("").trim().to_string())
+            .style(Style::default().fg(color))
+            .block(Block::bordered().title(title.to_string()))
+            .wrap(Wrap { trim: false }),
+        area,
+    );
+}
+
+/// How many steps of a scenario were correct, with the failed ones
+/// highlighted and their expected commands.
+fn scenario_result(command: &Command, steps: &[(String, Grade)]) -> Text<'static> {
+    let correct = steps.iter().filter(|(_, grade)| grade.is_correct()).count();
+    let mut lines = vec![Line::from(format!("{} of {} steps correct", correct, command.steps.len())).bold()];
+
+    for (step, (answer, grade)) in command.steps.iter().zip(steps) {
+        match grade {
+            Grade::Exact => {
+                lines.push(Line::from(format!("✓ {}", step.task)).green());
+                if let Some(response) = &step.response {
+                    lines.extend(response.trim().lines().map(|line| Line::from(format!("  {}", line))));
+                }
+            },
+            Grade::NearMiss { expected } | Grade::Wrong { expected } => {
+                lines.push(Line::from(format!("✗ {}", step.task)).red().add_modifier(Modifier::BOLD));
+                lines.push(Line::from(format!("  Answer: {}", answer)));
+                lines.push(Line::from(format!("  Expected: {}", expected)).green());
+            },
+        }
+    }
+
+    if let Some(extra) = &command.extra {
+        lines.push(Line::from(""));
+        lines.extend(extra.trim().lines().map(|line| Line::from(line.to_string()).cyan()));
+    }
+
+    let evaluation = Evaluation::from_steps(correct, command.steps.len());
+    lines.push(Line::from(""));
+    lines.push(Line::from(format!("Evaluation: {}", evaluation.get_num())).cyan());
+
+    Text::from(lines)
+}
+
+fn result_text(card: &Card, answer: &str, grade: &Grade, retried: bool) -> Text<'static> {
+    let (command, hints) = (&card.command, card.hints);
+    let mut lines: Vec<Line> = vec![];
+    let is_correct = grade.is_correct();
+
+    if is_correct {
+        lines.push(Line::from("CORRECT!").green().add_modifier(Modifier::BOLD));
+        if let Some(response) = &command.response {
+            lines.extend(response.trim().lines().map(|line| Line::from(line.to_string())));
+        }
+    }
+    else {
+        let (heading, expected) = match grade {
+            Grade::NearMiss { expected } => (Line::from("ALMOST").yellow(), expected.as_str()),
+            Grade::Wrong { expected } => (Line::from("INCORRECT").red(), expected.as_str()),
+            Grade::Exact => unreachable!(),
+        };
+        lines.push(heading.add_modifier(Modifier::BOLD));
+        lines.push(Line::from("Expected:"));
+
+        if command.kind.is_typed() {
+            lines.extend(command.commands.iter().map(|c| Line::from(c.clone()).green()));
+
+            let diff: Vec<Span> = diff(answer, expected).into_iter().map(|part| match part {
+                DiffPart::Same(text) => Span::from(text),
+                DiffPart::Extra(text) => Span::from(text).red().crossed_out(),
+                DiffPart::Missing(text) => Span::from(text).green().underlined(),
+            }).collect();
+            lines.push(Line::from([vec![Span::from("Diff: ")], diff].concat()));
+        }
+        else {
+            lines.push(Line::from(expected.to_string()).green());
+        }
+    }
+
+    if let Some(extra) = &command.extra {
+        lines.push(Line::from(""));
+        lines.extend(extra.trim().lines().map(|line| Line::from(line.to_string()).cyan()));
+    }
+
+    if let Mode::Learning = command.sr_data.mode {
+        let labels = if is_correct {
+            ["Hard", "Good", "Perfect"]
+        }
+        else {
+            ["Complete blackout", "Remembered", "Easy recall"]
+        };
+        let current_e_factor = (command.sr_data.e_factor * 100.0).floor();
+
+        lines.push(Line::from(""));
+        lines.push(Line::from(format!("Ease: {}%", current_e_factor)).cyan());
+        for (number, (label, evaluation)) in labels.iter().zip(Evaluation::choices(is_correct)).enumerate() {
+            let sr_data = compute_sr_data(&command.sr_data, &evaluation, false);
+            let change = (sr_data.e_factor * 100.0).floor() - current_e_factor;
+            lines.push(Line::from(vec![
+                format!("{}: {:<18} {:>3} days  ", number + 1, label, sr_data.interval).into(),
+                if change > 0.0 { format!("{:+}%", change).green() } else { format!("{:+}%", change).red() },
+            ]));
+        }
+
+        if is_correct && retried {
+            lines.push(Line::from("Answered after a retry, the rating is at most Hard.").yellow());
+        }
+        else if is_correct && hints > 0 {
+            let cap = if hints == 1 { "Good" } else { "Hard" };
+            lines.push(Line::from(format!("{} hints used, the rating is at most {}.", hints, cap)).yellow());
+        }
+        else if let Grade::NearMiss { .. } = grade {
+            lines.push(Line::from("It was only a typo. Press Enter to choose 3.").yellow());
+        }
+
+        if let Some((evaluation, baseline)) = &card.suggestion {
+            let choice = Evaluation::choices(true).iter().position(|choice| choice.get_num() == evaluation.get_num()).unwrap() + 1;
+            lines.push(Line::from(format!("Answered in {:.1}s, usually {:.1}s. Press Enter to choose {}.",
+                card.response_time.as_secs_f64(),
+                baseline.as_secs_f64(),
+                choice,
+            )).cyan());
+        }
+    }
+
+    Text::from(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(mode: Mode, command_times: Vec<Duration>) -> Card {
+        let mut command: Command = toml::from_str("task = \"Show the status\"\ncommands = [\"git status\"]").unwrap();
+        command.sr_data.mode = mode;
+        Card::new(command, vec![], (command_times, vec![]))
+    }
+
+    fn press(card: &mut Card, input: &mut Input, code: KeyCode) -> Action {
+        card.handle_key(KeyEvent::new(code, KeyModifiers::NONE), input)
+    }
+
+    fn answer(card: &mut Card, input: &mut Input, answer: &str) -> Action {
+        for c in answer.chars() {
+            press(card, input, KeyCode::Char(c));
+        }
+        press(card, input, KeyCode::Enter)
+    }
+
+    fn rating(action: Action) -> Option<i64> {
+        match action {
+            Action::Rate(evaluation) => Some(evaluation.get_num()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn learning_commands_are_rated_after_the_answer() {
+        let (mut card, mut input) = (card(Mode::Learning, vec![]), Input::default());
+
+        // Ratings are typed while answering
+        assert!(matches!(press(&mut card, &mut input, KeyCode::Char('2')), Action::None));
+        assert_eq!(input.text, "2");
+        press(&mut card, &mut input, KeyCode::Backspace);
+
+        assert!(matches!(answer(&mut card, &mut input, "git status"), Action::None));
+        assert!(matches!(card.stage, Stage::Answered { grade: Grade::Exact, .. }));
+        // Without a suggestion Enter has nothing to choose
+        assert!(matches!(press(&mut card, &mut input, KeyCode::Enter), Action::None));
+        assert_eq!(rating(press(&mut card, &mut input, KeyCode::Char('2'))), Some(4));
+    }
+
+    #[test]
+    fn new_commands_are_rated_on_enter() {
+        let (mut card, mut input) = (card(Mode::New, vec![]), Input::default());
+
+        answer(&mut card, &mut input, "git log");
+        assert!(matches!(card.stage, Stage::Answered { grade: Grade::Wrong { .. }, .. }));
+        assert_eq!(rating(press(&mut card, &mut input, KeyCode::Enter)), Some(0));
+    }
+
+    #[test]
+    fn a_typo_is_retried_once() {
+        let (mut card, mut input) = (card(Mode::Learning, vec![]), Input::default());
+
+        answer(&mut card, &mut input, "git stauts");
+        assert!(matches!(card.stage, Stage::Answering { retried: true, .. }));
+        assert_eq!(input.text, "git stauts");
+
+        input.set_text("git status".to_string());
+        press(&mut card, &mut input, KeyCode::Enter);
+        assert!(matches!(card.stage, Stage::Answered { retried: true, .. }));
+        assert_eq!(card.penalty(), 2);
+    }
+
+    #[test]
+    fn enter_chooses_the_suggested_rating() {
+        let minute = Duration::from_secs(60);
+        let (mut card, mut input) = (card(Mode::Learning, vec![minute; 3]), Input::default());
+
+        answer(&mut card, &mut input, "git status");
+        assert_eq!(card.suggestion.as_ref().map(|(evaluation, _)| evaluation.get_num()), Some(5));
+        assert_eq!(rating(press(&mut card, &mut input, KeyCode::Enter)), Some(5));
+
+        // A near miss suggests the best of the incorrect ratings
+        let (mut card, mut input) = (self::card(Mode::Learning, vec![minute; 3]), Input::default());
+        answer(&mut card, &mut input, "git stauts");
+        answer(&mut card, &mut input, "");
+        assert!(card.suggestion.is_none());
+        assert_eq!(rating(press(&mut card, &mut input, KeyCode::Enter)), Some(2));
+    }
+
+    #[test]
+    fn undo_is_only_offered_after_answering() {
+        let (mut card, mut input) = (card(Mode::Learning, vec![]), Input::default());
+
+        assert!(!matches!(press(&mut card, &mut input, KeyCode::Char('u')), Action::Undo));
+        assert!(matches!(card.stage, Stage::Answering { .. }));
+        assert_eq!(input.text, "u");
+
+        press(&mut card, &mut input, KeyCode::Backspace);
+        answer(&mut card, &mut input, "git status");
+        assert!(matches!(press(&mut card, &mut input, KeyCode::Char('u')), Action::Undo));
+        assert!(matches!(press(&mut card, &mut input, KeyCode::Esc), Action::Quit));
+    }
+}
@@ -1,9 +1,13 @@
+use std::fmt;
 use std::fs;
+use std::path::{Path, PathBuf};
 
 use chrono::{Utc, DateTime};
-use serde_derive::Deserialize;
+use serde_derive::{Deserialize, Serialize};
 
-use crate::Command;
+use crate::markdown::get_commands_from_markdown;
+use crate::tags;
+use crate::{CardKind, Command, SRData};
 
 pub fn get_current_date() -> DateTime<Utc> {
     let current_utc_time: DateTime<Utc> = Utc::now();
@@ -13,20 +17,612 @@ pub fn get_current_date() -> DateTime<Utc> {
     DateTime::from_utc(date_with_zero_time.unwrap(), Utc)
 }
 
-#[derive(Deserialize)]
-struct CommandsVec {
-    commands: Vec<Command>,
+/// Scheduling values given to the new commands of a deck.
+#[derive(Deserialize, Debug, Clone)]
+pub struct SchedulerOptions {
+    pub e_factor: Option<f64>,
+    pub interval: Option<i64>,
 }
 
-pub fn get_commands_from_toml(filename: &str) -> Vec<Command> {
+/// The optional `[deck]` table of a commands file. Its fields are used for
+/// every command of the file that doesn't set its own.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Deck {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub prompt: Option<String>,
+    pub context: Option<String>,
+    pub fixtures: Option<String>,
+    pub scheduler: Option<SchedulerOptions>,
+}
+
+impl Deck {
+    fn apply_defaults(&self, commands: &mut [Command]) {
+        for command in commands {
+            if command.tags.is_none() {
+                command.tags = self.tags.clone();
+            }
+            if command.prompt.is_none() {
+                command.prompt = self.prompt.clone();
+            }
+            if command.context.is_none() {
+                command.context = self.context.clone();
+            }
+            if command.fixtures.is_none() {
+                command.fixtures = self.fixtures.clone();
+            }
+
+            let Some(scheduler) = &self.scheduler else { continue };
+            if command.sr_data.review_count > 0 { continue; }
+
+            if let Some(e_factor) = scheduler.e_factor {
+                command.sr_data.e_factor = e_factor;
+            }
+            if let Some(interval) = scheduler.interval {
+                command.sr_data.interval = interval;
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CommandsVec {
+    pub deck: Option<Deck>,
+    pub commands: Vec<Command>,
+}
+
+impl CommandsVec {
+    fn new(deck: Option<Deck>, mut commands: Vec<Command>) -> Self {
+        if let Some(deck) = &deck {
+            deck.apply_defaults(&mut commands);
+        }
+        Self { deck, commands }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq)]
+pub enum ImportFormat {
+    Toml,
+    Json,
+    Yaml,
+    Jsonl,
+    Markdown,
+}
+
+impl ImportFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+
+        match extension.as_str() {
+            "toml" => Some(ImportFormat::Toml),
+            "json" => Some(ImportFormat::Json),
+            "yaml" | "yml" => Some(ImportFormat::Yaml),
+            "jsonl" | "ndjson" => Some(ImportFormat::Jsonl),
+            "md" | "markdown" => Some(ImportFormat::Markdown),
+            _ => None,
+        }
+    }
+}
+
+/// An error found while reading a commands file. `line` and `card` are
+/// filled in whenever the parser is able to tell where the problem is.
+#[derive(Debug)]
+pub struct ImportError {
+    pub file: String,
+    pub line: Option<usize>,
+    pub card: Option<usize>,
+    pub message: String,
+}
+
+impl ImportError {
+    pub(crate) fn new(file: &str, message: impl ToString) -> Self {
+        Self {
+            file: file.to_string(),
+            line: None,
+            card: None,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        if let Some(card) = self.card {
+            write!(f, " (card {})", card)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// A file found by `find_deck_files`, with the tag given to its commands.
+pub struct DeckFile {
+    pub path: String,
+    pub tag: Option<String>,
+    /// Why the file, or the directory at `path`, could not be read
+    pub error: Option<String>,
+}
+
+/// Finds the deck files matched by `path`, which can be a file, a directory
+/// that is walked recursively, or a glob pattern. Commands in nested files
+/// are tagged with the directory they are in, relative to `path`, as a
+/// nested tag such as `git::branches`. The entries that can't be read are
+/// returned with their error, after the files.
+pub fn find_deck_files(path: &str, format: Option<&ImportFormat>) -> anyhow::Result<Vec<DeckFile>> {
+    let root = Path::new(path);
+
+    if root.is_file() {
+        return Ok(vec![DeckFile { path: path.to_string(), tag: None, error: None }]);
+    }
+
+    let mut unreadable: Vec<DeckFile> = vec![];
+    let (base, mut files) = if root.is_dir() {
+        let mut files = vec![];
+        walk_directory(root, &mut files, &mut unreadable);
+        (root.to_path_buf(), files)
+    }
+    else if path.contains(['*', '?', '[']) {
+        let base: PathBuf = root.components()
+            .take_while(|component| !component.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+            .collect();
+        let mut files = vec![];
+        for entry in glob::glob(path)? {
+            match entry {
+                Ok(entry) if entry.is_file() => files.push(entry),
+                Ok(_) => {},
+                Err(error) => unreadable.push(DeckFile {
+                    path: error.path().to_string_lossy().to_string(),
+                    tag: None,
+                    error: Some(error.error().to_string()),
+                }),
+            }
+        }
+        (base, files)
+    }
+    else {
+        anyhow::bail!("No such file or directory: {path}");
+    };
 
-    let contents = fs::read_to_string(filename).unwrap_or_else(|error| {
-        panic!("Cound not read commands file: {error}");
+    files.retain(|file| match (ImportFormat::from_path(file), format) {
+        (Some(detected), Some(format)) => detected == *format,
+        (detected, _) => detected.is_some(),
     });
+    files.sort();
 
-    let commands_vec : CommandsVec = toml::from_str(&contents).unwrap_or_else(|error: toml::de::Error| {
-        panic!("Invalid toml file: {}", error);
+    let files = files.into_iter().map(|file| {
+        let tag = file.parent()
+            .and_then(|parent| parent.strip_prefix(&base).ok())
+            .map(|relative| relative.components()
+                .map(|component| component.as_os_str().to_string_lossy().to_string())
+                .collect::<Vec<String>>()
+                .join(tags::SEPARATOR))
+            .filter(|tag| !tag.is_empty());
+
+        DeckFile { path: file.to_string_lossy().to_string(), tag, error: None }
     });
-    
-    commands_vec.commands
-}
\ No newline at end of file
+
+    Ok(files.chain(unreadable).collect())
+}
+
+/// Adds the files in `directory` and its subdirectories to `files`, and the
+/// directories that can't be read to `unreadable`. Symlinked directories are
+/// skipped, as they may link back to a parent.
+fn walk_directory(directory: &Path, files: &mut Vec<PathBuf>, unreadable: &mut Vec<DeckFile>) {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(error) => {
+            unreadable.push(DeckFile { path: directory.to_string_lossy().to_string(), tag: None, error: Some(error.to_string()) });
+            return;
+        },
+    };
+
+    for entry in entries {
+        let (path, file_type) = match entry.and_then(|entry| Ok((entry.path(), entry.file_type()?))) {
+            Ok(entry) => entry,
+            Err(error) => {
+                unreadable.push(DeckFile { path: directory.to_string_lossy().to_string(), tag: None, error: Some(error.to_string()) });
+                continue;
+            },
+        };
+
+        if file_type.is_dir() {
+            walk_directory(&path, files, unreadable);
+        }
+        else if !(file_type.is_symlink() && path.is_dir()) {
+            files.push(path);
+        }
+    }
+}
+
+/// Reads a commands file, using `format` or, if it is not given, the file extension.
+pub fn get_commands_from_file(filename: &str, format: Option<&ImportFormat>) -> Result<CommandsVec, ImportError> {
+    let format = match format {
+        Some(format) => format.clone(),
+        None => ImportFormat::from_path(Path::new(filename)).ok_or_else(|| {
+            ImportError::new(filename, "Unknown file format, use --format to specify it")
+        })?,
+    };
+
+    let contents = fs::read_to_string(filename).map_err(|error| {
+        ImportError::new(filename, format!("Could not read commands file: {error}"))
+    })?;
+
+    let mut commands_vec = parse_commands(filename, &contents, &format)?;
+
+    // Fixture directories are written relative to the file that declares them
+    let directory = Path::new(filename).parent().unwrap_or(Path::new(""));
+    for command in commands_vec.commands.iter_mut() {
+        if let Some(fixtures) = &command.fixtures {
+            let path = directory.join(fixtures);
+            let path = path.canonicalize().unwrap_or(path);
+            command.fixtures = Some(path.to_string_lossy().to_string());
+        }
+    }
+
+    Ok(commands_vec)
+}
+
+pub fn get_commands_from_toml(filename: &str) -> Result<Vec<Command>, ImportError> {
+    Ok(get_commands_from_file(filename, Some(&ImportFormat::Toml))?.commands)
+}
+
+fn parse_commands(filename: &str, contents: &str, format: &ImportFormat) -> Result<CommandsVec, ImportError> {
+    let commands_vec = parse_cards(filename, contents, format)?;
+
+    // Only scenario cards, whose steps have the commands, and cloze cards
+    // with the gaps in `cloze` can leave them out
+    for (index, command) in commands_vec.commands.iter().enumerate() {
+        let missing = match command.kind {
+            CardKind::Scenario => command.steps.is_empty().then_some("steps"),
+            CardKind::Cloze if command.cloze.is_some() => None,
+            _ => command.commands.is_empty().then_some("commands"),
+        };
+        if let Some(field) = missing {
+            return Err(ImportError {
+                card: Some(index + 1),
+                ..ImportError::new(filename, format!("Invalid card: missing field `{field}`"))
+            });
+        }
+    }
+
+    Ok(commands_vec)
+}
+
+fn parse_cards(filename: &str, contents: &str, format: &ImportFormat) -> Result<CommandsVec, ImportError> {
+    let parsed = match format {
+        ImportFormat::Toml => toml::from_str::<CommandsVec>(contents)
+            .map_err(|error| (error.line_col().map(|(line, _)| line + 1), error.to_string())),
+        ImportFormat::Json => serde_json::from_str::<CommandsVec>(contents)
+            .map_err(|error| (Some(error.line()), error.to_string())),
+        ImportFormat::Yaml => serde_yaml::from_str::<CommandsVec>(contents)
+            .map_err(|error| (error.location().map(|location| location.line()), error.to_string())),
+        ImportFormat::Jsonl => return Ok(CommandsVec::new(None, parse_json_lines(filename, contents)?)),
+        ImportFormat::Markdown => return Ok(CommandsVec::new(None, get_commands_from_markdown(filename, contents)?)),
+    };
+
+    let (line, message) = match parsed {
+        Ok(commands_vec) => return Ok(CommandsVec::new(commands_vec.deck, commands_vec.commands)),
+        Err(error) => error,
+    };
+
+    // The typed parser only knows the line of the error, so the file is read
+    // again as a generic document to find out which card is invalid. This
+    // also accepts a plain list of cards without the 'commands' key.
+    let document: Result<serde_json::Value, String> = match format {
+        ImportFormat::Toml => toml::from_str::<toml::Value>(contents)
+            .map_err(|error| error.to_string())
+            .and_then(|value| serde_json::to_value(value).map_err(|error| error.to_string())),
+        ImportFormat::Json => serde_json::from_str(contents).map_err(|error| error.to_string()),
+        ImportFormat::Yaml => serde_yaml::from_str(contents).map_err(|error| error.to_string()),
+        ImportFormat::Jsonl | ImportFormat::Markdown => unreachable!(),
+    };
+
+    let invalid_file = ImportError {
+        line,
+        ..ImportError::new(filename, format!("Invalid file: {message}"))
+    };
+
+    let (cards, is_list) = match document {
+        Ok(serde_json::Value::Array(cards)) => (cards, true),
+        Ok(serde_json::Value::Object(mut document)) => {
+            if let Some(deck) = document.remove("deck") {
+                serde_json::from_value::<Deck>(deck).map_err(|error| ImportError {
+                    line,
+                    ..ImportError::new(filename, format!("Invalid deck: {error}"))
+                })?;
+            }
+
+            match document.remove("commands") {
+                Some(serde_json::Value::Array(cards)) => (cards, false),
+                _ => return Err(invalid_file),
+            }
+        },
+        _ => return Err(invalid_file),
+    };
+
+    let mut commands = vec![];
+    for (index, card) in cards.into_iter().enumerate() {
+        let command: Command = serde_json::from_value(card).map_err(|error| ImportError {
+            line: if is_list { None } else { line },
+            card: Some(index + 1),
+            ..ImportError::new(filename, format!("Invalid card: {error}"))
+        })?;
+        commands.push(command);
+    }
+
+    // Only a plain list of cards can be valid here, since a document with a
+    // 'commands' key would have been read by the typed parser.
+    if is_list {
+        Ok(CommandsVec::new(None, commands))
+    }
+    else {
+        Err(invalid_file)
+    }
+}
+
+fn parse_json_lines(filename: &str, contents: &str) -> Result<Vec<Command>, ImportError> {
+    let mut commands = vec![];
+
+    for (index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() { continue; }
+
+        let command: Command = serde_json::from_str(line).map_err(|error| ImportError {
+            line: Some(index + 1),
+            card: Some(commands.len() + 1),
+            ..ImportError::new(filename, format!("Invalid card: {error}"))
+        })?;
+        commands.push(command);
+    }
+
+    Ok(commands)
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+pub enum ExportFormat {
+    Toml,
+    Json,
+    Csv,
+}
+
+/// A command as it is written by `export_commands`. The scheduling state is
+/// only included when `progress` is set, under the same key that
+/// `get_commands_from_toml` reads it back from.
+#[derive(Serialize)]
+struct ExportedCommand<'a> {
+    #[serde(flatten)]
+    command: &'a Command,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    progress: Option<&'a SRData>,
+}
+
+#[derive(Serialize)]
+struct ExportedCommandsVec<'a> {
+    commands: Vec<ExportedCommand<'a>>,
+}
+
+pub fn export_commands(commands: &[Command], format: &ExportFormat, with_progress: bool) -> anyhow::Result<String> {
+    let commands_vec = ExportedCommandsVec {
+        commands: commands.iter().map(|command| ExportedCommand {
+            command,
+            progress: if with_progress { Some(&command.sr_data) } else { None },
+        }).collect(),
+    };
+
+    let output = match format {
+        ExportFormat::Toml => toml::to_string(&commands_vec)?,
+        ExportFormat::Json => serde_json::to_string_pretty(&commands_vec)?,
+        ExportFormat::Csv => export_commands_to_csv(commands, with_progress)?,
+    };
+
+    Ok(output)
+}
+
+/// Writes a command per row. The commands, tags and steps are JSON arrays,
+/// since no separator is safe in shell commands.
+fn export_commands_to_csv(commands: &[Command], with_progress: bool) -> anyhow::Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+
+    let mut header = vec!["task", "clues", "context", "prompt", "commands", "response", "extra", "tags", "type", "flag", "fixtures", "cloze", "group", "steps"];
+    if with_progress {
+        header.extend(["created", "last_review", "mode", "review_count", "n", "e_factor", "interval"]);
+    }
+    writer.write_record(&header)?;
+
+    for command in commands {
+        let mut record = vec![
+            command.task.clone(),
+            command.clues.clone().unwrap_or_default(),
+            command.context.clone().unwrap_or_default(),
+            command.prompt.clone().unwrap_or_default(),
+            serde_json::to_string(&command.commands)?,
+            command.response.clone().unwrap_or_default(),
+            command.extra.clone().unwrap_or_default(),
+            serde_json::to_string(&command.tags.clone().unwrap_or_default())?,
+            serde_json::to_value(&command.kind)?.as_str().unwrap_or_default().to_string(),
+            command.flag.clone().unwrap_or_default(),
+            command.fixtures.clone().unwrap_or_default(),
+            command.cloze.clone().unwrap_or_default(),
+            command.group.clone().unwrap_or_default(),
+            match command.steps.is_empty() {
+                true => String::new(),
+                false => serde_json::to_string(&command.steps)?,
+            },
+        ];
+
+        if with_progress {
+            let sr_data = &command.sr_data;
+            record.extend([
+                sr_data.created.to_rfc3339(),
+                sr_data.last_review.map(|review| review.to_rfc3339()).unwrap_or_default(),
+                sr_data.mode.to_string(),
+                sr_data.review_count.to_string(),
+                sr_data.n.to_string(),
+                sr_data.e_factor.to_string(),
+                sr_data.interval.to_string(),
+            ]);
+        }
+        writer.write_record(&record)?;
+    }
+
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::utilities::{export_commands, find_deck_files, parse_commands, CommandsVec, ExportFormat, ImportFormat};
+    use crate::{CardKind, Command, Mode, SRData};
+
+    #[test]
+    fn exported_toml_round_trips() {
+        let command = Command {
+            id: Some(1),
+            task: String::from("Show the working tree status"),
+            clues: None,
+            context: None,
+            prompt: Some(String::from("$")),
+            commands: vec![String::from("git status")],
+            response: None,
+            extra: None,
+            tags: Some(vec![String::from("git")]),
+            fixtures: None,
+            kind: CardKind::Recall,
+            flag: None,
+            cloze: None,
+            group: None,
+            steps: vec![],
+            sr_data: SRData {
+                mode: Mode::Learning,
+                n: 3,
+                interval: 12,
+                ..Default::default()
+            },
+        };
+
+        let exported = export_commands(&[command], &ExportFormat::Toml, true).unwrap();
+        let imported: CommandsVec = toml::from_str(&exported).unwrap();
+
+        assert_eq!(imported.commands.len(), 1);
+        assert_eq!(imported.commands[0].commands, vec!["git status"]);
+        assert_eq!(imported.commands[0].sr_data.interval, 12);
+        assert!(matches!(imported.commands[0].sr_data.mode, Mode::Learning));
+    }
+
+    #[test]
+    fn exported_csv_has_a_row_per_command() {
+        let command: Command = toml::from_str(r#"
+            task = "Build in a directory"
+            commands = ["cd x; make", "make -C x"]
+            tags = ["make"]
+            group = "build"
+        "#).unwrap();
+
+        let exported = export_commands(&[command], &ExportFormat::Csv, false).unwrap();
+        let mut reader = csv::Reader::from_reader(exported.as_bytes());
+        let record = reader.records().next().unwrap().unwrap();
+
+        assert_eq!(exported.lines().count(), 2);
+        assert_eq!(serde_json::from_str::<Vec<String>>(&record[4]).unwrap(), vec!["cd x; make", "make -C x"]);
+        assert_eq!(&record[7], "[\"make\"]");
+        assert_eq!(&record[12], "build");
+    }
+
+    #[test]
+    fn invalid_card_is_reported_with_its_index() {
+        let contents = "commands:\n  - task: a\n    commands: [x]\n  - task: b\n";
+        let error = parse_commands("deck.yaml", contents, &ImportFormat::Yaml).unwrap_err();
+
+        assert_eq!(error.card, Some(2));
+
+        let contents = "{\"task\": \"a\", \"commands\": [\"x\"]}\n{\"task\": 1}\n";
+        let error = parse_commands("deck.jsonl", contents, &ImportFormat::Jsonl).unwrap_err();
+
+        assert_eq!(error.line, Some(2));
+        assert_eq!(error.card, Some(2));
+    }
+
+    #[test]
+    fn deck_defaults_are_overridden_by_cards() {
+        let contents = r#"
+            [deck]
+            name = "PowerShell"
+            tags = ["powershell"]
+            prompt = "PS>"
+
+            [deck.scheduler]
+            e_factor = 2.0
+
+            [[commands]]
+            task = "List the files"
+            commands = ["Get-ChildItem"]
+
+            [[commands]]
+            task = "List the files with cmd"
+            prompt = ">"
+            tags = ["cmd"]
+            commands = ["dir"]
+        "#;
+        let commands = parse_commands("deck.toml", contents, &ImportFormat::Toml).unwrap().commands;
+
+        assert_eq!(commands[0].prompt.as_deref(), Some("PS>"));
+        assert_eq!(commands[0].tags, Some(vec![String::from("powershell")]));
+        assert_eq!(commands[0].sr_data.e_factor, 2.0);
+        assert_eq!(commands[1].prompt.as_deref(), Some(">"));
+        assert_eq!(commands[1].tags, Some(vec![String::from("cmd")]));
+    }
+
+    #[test]
+    fn nested_deck_directories_give_nested_tags() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("git").join("branches")).unwrap();
+        fs::write(root.path().join("git").join("branches").join("merge.toml"), "").unwrap();
+
+        let files = find_deck_files(&root.path().to_string_lossy(), None).unwrap();
+
+        assert_eq!(files[0].tag.as_deref(), Some("git::branches"));
+        assert!(crate::tags::is_within(files[0].tag.as_deref().unwrap(), "git"));
+    }
+
+    #[test]
+    fn deck_directories_are_walked_for_the_format() {
+        let root = tempfile::tempdir().unwrap();
+        for directory in ["git", "k8s"] {
+            fs::create_dir(root.path().join(directory)).unwrap();
+        }
+        for file in ["basics.toml", "git/log.yaml", "git/notes.txt", "k8s/pods.toml"] {
+            fs::write(root.path().join(file), "").unwrap();
+        }
+        let path = root.path().to_string_lossy();
+
+        let files = find_deck_files(&path, None).unwrap();
+        let tags: Vec<Option<&str>> = files.iter().map(|file| file.tag.as_deref()).collect();
+        // Files at the root are not tagged, and only known formats are imported
+        assert_eq!(tags, vec![None, Some("git"), Some("k8s")]);
+        assert!(files[1].path.ends_with("log.yaml"));
+
+        let files = find_deck_files(&path, Some(&ImportFormat::Toml)).unwrap();
+        let tags: Vec<Option<&str>> = files.iter().map(|file| file.tag.as_deref()).collect();
+        assert_eq!(tags, vec![None, Some("k8s")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_directories_are_not_walked() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir(root.path().join("git")).unwrap();
+        fs::write(root.path().join("git").join("log.toml"), "").unwrap();
+        // A loop back to the root
+        std::os::unix::fs::symlink(root.path(), root.path().join("git").join("again")).unwrap();
+
+        let files = find_deck_files(&root.path().to_string_lossy(), None).unwrap();
+
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].tag.as_deref(), Some("git"));
+    }
+}